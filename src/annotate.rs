@@ -0,0 +1,96 @@
+use crate::params::EvalParams;
+use crate::pgn::{Game, PgnMove};
+use crate::search::{fixed_search, extract_pv};
+use crate::tablebase::Tablebases;
+use crate::transposition::TranspositionTable;
+
+use shakmaty::{Chess, Color, Move, Position, san::San};
+
+// Clearly won or lost positions only count up to this score, so a won position getting slightly less won isn't a blunder
+const SCORE_CAP: i32 = 1000;
+
+// How each position is searched and how large a drop in the score is flagged
+pub struct AnnotateSettings {
+    pub depth: usize,             // Maximum search depth
+    pub move_time: Option<u64>,   // Milliseconds per position, or None to always search to the full depth
+    pub inaccuracy: i32,          // Score drops in centipawns marked ?!
+    pub mistake: i32,             // ... marked ?
+    pub blunder: i32,             // ... marked ??
+}
+
+impl Default for AnnotateSettings {
+    fn default() -> Self {
+        Self {
+            depth: 8,
+            move_time: None,
+            inaccuracy: 50,
+            mistake: 100,
+            blunder: 300,
+        }
+    }
+}
+
+// The search result of one position of the game
+struct Analysis {
+    best_move: Option<Move>,
+    score: i32,       // From the side to move's point of view
+    pv: Vec<Move>,    // Starting with the best move
+}
+
+// Search every position of the main line, record the evaluation after each move, and mark the moves that lost
+// too much against the best move with a NAG, a comment and a variation showing the better line
+pub fn annotate_game(game: &mut Game, settings: &AnnotateSettings, eval_params: &EvalParams, tablebase: Option<&Tablebases>) {
+    let mut t_table = TranspositionTable::new();
+    let analyses: Vec<Analysis> = game.positions().iter()
+        .map(|board| analyse(board, settings, &mut t_table, eval_params, tablebase))
+        .collect();
+
+    let mut board = game.start.clone();
+    for (index, pgn_move) in game.moves.iter_mut().enumerate() {
+        let (before, after) = (&analyses[index], &analyses[index + 1]);
+        // A finished game has nothing left to evaluate
+        let white_score = if board.turn() == Color::White { -after.score } else { after.score };
+        if after.best_move.is_some() { pgn_move.eval = Some(white_score); }
+
+        // Compare the best score with the score of the move played, both for the side that moved
+        let best = before.score.clamp(-SCORE_CAP, SCORE_CAP);
+        let played = (-after.score).clamp(-SCORE_CAP, SCORE_CAP);
+        let drop = if before.best_move.as_ref() == Some(&pgn_move.mv) { 0 } else { best - played };
+
+        let label = if drop >= settings.blunder { Some(("Blunder", 4)) }
+        else if drop >= settings.mistake { Some(("Mistake", 2)) }
+        else if drop >= settings.inaccuracy { Some(("Inaccuracy", 6)) }
+        else { None };
+
+        if let (Some((label, nag)), Some(best_move)) = (label, &before.best_move) {
+            // Our verdict replaces any move assessment ($1 to $6) already in the game
+            pgn_move.nags.retain(|nag| !(1..=6).contains(nag));
+            pgn_move.nags.push(nag);
+            let text = format!("{}. {} was best.", label, San::from_move(&board, best_move));
+            pgn_move.comment = Some(match pgn_move.comment.take() {
+                Some(comment) => format!("{} {}", text, comment),
+                None => text,
+            });
+
+            // The better line goes in front of any variation already in the game
+            let mut variation: Vec<PgnMove> = before.pv.iter().cloned().map(PgnMove::new).collect();
+            if let Some(first) = variation.first_mut() {
+                first.eval = Some(if board.turn() == Color::White { before.score } else { -before.score });
+            }
+            pgn_move.variations.insert(0, variation);
+        }
+        board.play_unchecked(&pgn_move.mv);
+    }
+
+    let annotator = match settings.move_time {
+        Some(move_time) => format!("je_bot {}ms", move_time),
+        None => format!("je_bot depth {}", settings.depth),
+    };
+    game.set_tag("Annotator", &annotator);
+}
+
+fn analyse(board: &Chess, settings: &AnnotateSettings, t_table: &mut TranspositionTable, eval_params: &EvalParams, tablebase: Option<&Tablebases>) -> Analysis {
+    let (best_move, score, depth) = fixed_search(board, settings.depth, settings.move_time, t_table, eval_params, tablebase);
+    let pv = best_move.as_ref().map_or(Vec::new(), |best_move| extract_pv(board, best_move, t_table, depth));
+    Analysis { best_move, score, pv }
+}
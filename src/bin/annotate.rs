@@ -0,0 +1,58 @@
+use chessai::annotate::{annotate_game, AnnotateSettings};
+use chessai::params::EvalParams;
+use chessai::pgn::{parse_pgn, write_game};
+use chessai::tablebase::Tablebases;
+
+use std::env;
+use std::fs;
+use std::process;
+
+const USAGE: &str = "Usage: annotate <pgn file> [--out <file>] [--depth <n>] [--movetime <ms>] [--params <file>] [--syzygy <path>] [--inaccuracy <cp>] [--mistake <cp>] [--blunder <cp>]";
+
+// Analyse every game of a PGN file and write it back with evaluations and the mistakes marked.
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let Some(pgn_path) = args.first().filter(|path| !path.starts_with("--")) else {
+        eprintln!("{}", USAGE);
+        process::exit(1);
+    };
+    let flag = |name: &str| args.iter().position(|arg| arg == name).and_then(|i| args.get(i + 1));
+    let number = |name: &str, default: i32| flag(name).and_then(|value| value.parse().ok()).unwrap_or(default);
+
+    // A move time alone searches as deep as the time allows
+    let defaults = AnnotateSettings::default();
+    let move_time: Option<u64> = flag("--movetime").and_then(|time| time.parse().ok());
+    let settings = AnnotateSettings {
+        depth: flag("--depth").and_then(|depth| depth.parse().ok()).unwrap_or(if move_time.is_some() { 64 } else { defaults.depth }),
+        move_time,
+        inaccuracy: number("--inaccuracy", defaults.inaccuracy),
+        mistake: number("--mistake", defaults.mistake),
+        blunder: number("--blunder", defaults.blunder),
+    };
+
+    let mut params = EvalParams::default();
+    if let Some(path) = flag("--params") {
+        params = params.load_file(path).unwrap_or_else(|error| { eprintln!("{}", error); process::exit(1); });
+    }
+    let tablebase = flag("--syzygy").map(|path| Tablebases::load(path, 7).unwrap_or_else(|error| { eprintln!("{}", error); process::exit(1); }));
+    let out_path = flag("--out").cloned().unwrap_or_else(|| "annotated.pgn".to_string());
+
+    let text = fs::read_to_string(pgn_path).unwrap_or_else(|error| { eprintln!("could not read {}: {}", pgn_path, error); process::exit(1); });
+    let mut output = String::new();
+    for (index, game) in parse_pgn(&text).into_iter().enumerate() {
+        match game {
+            Ok(mut game) => {
+                eprintln!("Annotating game {} ({} moves)", index + 1, game.moves.len());
+                annotate_game(&mut game, &settings, &params, tablebase.as_ref());
+                output.push_str(&write_game(&game));
+            }
+            Err(error) => eprintln!("Skipping {}", error),
+        }
+    }
+
+    if let Err(error) = fs::write(&out_path, output) {
+        eprintln!("could not write {}: {}", out_path, error);
+        process::exit(1);
+    }
+    eprintln!("Annotated games written to {}", out_path);
+}
@@ -0,0 +1,44 @@
+use chessai::bookgen::{BookBuilder, BookFilter};
+
+use std::env;
+use std::fs;
+use std::process;
+
+const USAGE: &str = "Usage: bookgen <pgn files...> [--out <file>] [--plies <n>] [--min-elo <n>] [--results <1-0,0-1,1/2-1/2>] [--min-games <n>]";
+
+// Build a Polyglot opening book from PGN game collections.
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let flag = |name: &str| args.iter().position(|arg| arg == name).and_then(|i| args.get(i + 1));
+
+    // Everything that isn't a flag or a flag's value is a PGN file
+    let mut pgn_paths: Vec<&String> = Vec::new();
+    let mut i = 0;
+    while i < args.len() {
+        if args[i].starts_with("--") { i += 2; } else { pgn_paths.push(&args[i]); i += 1; }
+    }
+    if pgn_paths.is_empty() {
+        eprintln!("{}", USAGE);
+        process::exit(1);
+    }
+
+    let filter = BookFilter {
+        max_plies: flag("--plies").and_then(|plies| plies.parse().ok()).unwrap_or(32),
+        min_elo: flag("--min-elo").and_then(|elo| elo.parse().ok()).unwrap_or(0),
+        results: flag("--results").map_or("1-0,0-1,1/2-1/2", |results| results.as_str()).split(',').map(|result| result.trim().to_string()).collect(),
+        min_games: flag("--min-games").and_then(|games| games.parse().ok()).unwrap_or(1),
+    };
+    let out_path = flag("--out").cloned().unwrap_or_else(|| "book.bin".to_string());
+
+    let mut builder = BookBuilder::new();
+    for path in pgn_paths {
+        let text = fs::read_to_string(path).unwrap_or_else(|error| { eprintln!("could not read {}: {}", path, error); process::exit(1); });
+        builder.add_pgn(&text, &filter);
+    }
+    println!("Used {} games, skipped {}", builder.games_used, builder.games_skipped);
+
+    match builder.write(&out_path, &filter) {
+        Ok(entries) => println!("Wrote {} entries to {}", entries, out_path),
+        Err(error) => { eprintln!("{}", error); process::exit(1); }
+    }
+}
@@ -0,0 +1,70 @@
+use chessai::epd::load_epd;
+use chessai::params::EvalParams;
+use chessai::search::fixed_search;
+use chessai::tablebase::Tablebases;
+use chessai::transposition::TranspositionTable;
+
+use std::env;
+use std::process;
+
+use shakmaty::san::SanPlus;
+
+const USAGE: &str = "Usage: epd <epd file> [--depth <n>] [--movetime <ms>] [--params <file>] [--syzygy <path>]";
+
+// Run a test suite such as WAC or STS and report which positions the engine solves.
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let Some(epd_path) = args.first().filter(|path| !path.starts_with("--")) else {
+        eprintln!("{}", USAGE);
+        process::exit(1);
+    };
+    let flag = |name: &str| args.iter().position(|arg| arg == name).and_then(|i| args.get(i + 1));
+
+    // Without a depth or a move time each position gets one second
+    let move_time: Option<u64> = flag("--movetime").and_then(|time| time.parse().ok());
+    let depth: Option<usize> = flag("--depth").and_then(|depth| depth.parse().ok());
+    let move_time = if depth.is_none() { move_time.or(Some(1000)) } else { move_time };
+    let depth = depth.unwrap_or(64);
+
+    let mut params = EvalParams::default();
+    if let Some(path) = flag("--params") {
+        params = params.load_file(path).unwrap_or_else(|error| { eprintln!("{}", error); process::exit(1); });
+    }
+    let tablebase = flag("--syzygy").map(|path| Tablebases::load(path, 7).unwrap_or_else(|error| { eprintln!("{}", error); process::exit(1); }));
+    let positions = load_epd(epd_path).unwrap_or_else(|error| { eprintln!("{}", error); process::exit(1); });
+
+    let (mut passed, mut points, mut max_points) = (0, 0, 0);
+    let mut results: Vec<String> = Vec::new();
+    for (index, position) in positions.iter().enumerate() {
+        // Every position starts with an empty table so earlier positions can't help
+        let mut t_table = TranspositionTable::new();
+        let (best_move, score, depth_reached) = fixed_search(&position.board, depth, move_time, &mut t_table, &params, tablebase.as_ref());
+        let id = if position.id.is_empty() { format!("#{}", index + 1) } else { position.id.clone() };
+        max_points += position.max_points();
+
+        // Checkmate and stalemate positions have no move to test, they still count as a failed position
+        let Some(best_move) = best_move else {
+            results.push(format!("{:<12} FAIL {:<7} no legal move", id, "-"));
+            continue;
+        };
+
+        let pass = position.passes(&best_move);
+        if pass { passed += 1; }
+        points += position.points(&best_move);
+
+        let san = |mv| SanPlus::from_move(position.board.clone(), mv).to_string();
+        let mut expected: Vec<String> = Vec::new();
+        if !position.best_moves.is_empty() { expected.push(format!("bm {}", position.best_moves.iter().map(san).collect::<Vec<_>>().join(" "))); }
+        if !position.avoid_moves.is_empty() { expected.push(format!("am {}", position.avoid_moves.iter().map(san).collect::<Vec<_>>().join(" "))); }
+        if position.max_points() > 0 { expected.push(format!("{}/{} points", position.points(&best_move), position.max_points())); }
+
+        let result = format!("{:<12} {} {:<7} depth {:>2} score {:>6}  {}", id, if pass { "pass" } else { "FAIL" }, san(&best_move), depth_reached, score, expected.join(", "));
+        results.push(result);
+    }
+
+    // The search prints its info lines as it goes, so the results are repeated together at the end
+    println!();
+    for result in &results { println!("{}", result); }
+    println!("Passed {}/{} ({:.1}%)", passed, positions.len(), 100.0 * passed as f64 / positions.len().max(1) as f64);
+    if max_points > 0 { println!("STS score {}/{} ({:.1}%)", points, max_points, 100.0 * points as f64 / max_points as f64); }
+}
@@ -0,0 +1,40 @@
+use chessai::params::EvalParams;
+use chessai::tuner::{load_positions, find_k, mean_squared_error, local_search};
+
+use std::env;
+use std::fs;
+use std::process;
+
+// Texel tuning of the evaluation weights.
+// Usage: tune <positions file> [--params <start file>] [--out <file>] [--passes <n>]
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let Some(positions_path) = args.first().filter(|path| !path.starts_with("--")) else {
+        eprintln!("Usage: tune <positions file> [--params <start file>] [--out <file>] [--passes <n>]");
+        process::exit(1);
+    };
+    let flag = |name: &str| args.iter().position(|arg| arg == name).and_then(|i| args.get(i + 1));
+
+    // Start from the default profile, optionally overridden by an EvalFile
+    let mut params = EvalParams::default();
+    if let Some(path) = flag("--params") {
+        params = params.load_file(path).unwrap_or_else(|error| { eprintln!("{}", error); process::exit(1); });
+    }
+    let out_path = flag("--out").cloned().unwrap_or_else(|| "tuned_params.txt".to_string());
+    let passes: usize = flag("--passes").and_then(|passes| passes.parse().ok()).unwrap_or(100);
+
+    let positions = load_positions(positions_path, &params).unwrap_or_else(|error| { eprintln!("{}", error); process::exit(1); });
+    println!("Loaded {} positions", positions.len());
+
+    let k = find_k(&positions, &params);
+    println!("K = {:.4}, initial error {:.6}", k, mean_squared_error(&positions, &params, k));
+
+    // Save after every pass so a long run can be interrupted without losing progress
+    local_search(&positions, &params, k, passes, |pass, error, params| {
+        println!("Pass {} error {:.6}", pass, error);
+        if let Err(error) = fs::write(&out_path, params.to_text()) {
+            eprintln!("could not write {}: {}", out_path, error);
+        }
+    });
+    println!("Tuned parameters written to {}", out_path);
+}
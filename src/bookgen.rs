@@ -0,0 +1,117 @@
+use crate::book::{polyglot_key, encode_move};
+use crate::pgn::{parse_pgn, Game};
+
+use std::fs;
+
+use rustc_hash::FxHashMap;
+use shakmaty::{Color, Position};
+
+// Which games and moves go into the book
+pub struct BookFilter {
+    pub max_plies: usize,       // Only the first plies of each game are recorded
+    pub min_elo: u32,           // Both players need at least this rating, 0 accepts unrated games
+    pub results: Vec<String>,   // Accepted results, such as "1-0", "0-1" and "1/2-1/2"
+    pub min_games: u32,         // Moves played fewer times are left out of the book
+}
+
+// Results of a move from the point of view of the side that played it
+#[derive(Default, Copy, Clone)]
+pub struct MoveStats {
+    pub wins: u32,
+    pub draws: u32,
+    pub losses: u32,
+}
+
+impl MoveStats {
+    pub fn games(&self) -> u32 {
+        self.wins + self.draws + self.losses
+    }
+
+    // Polyglot style points, two per win and one per draw, so the weight grows with both popularity and win rate
+    pub fn points(&self) -> u32 {
+        2 * self.wins + self.draws
+    }
+}
+
+// Move statistics per position, keyed by the Polyglot hash and the Polyglot move encoding
+pub struct BookBuilder {
+    pub stats: FxHashMap<(u64, u16), MoveStats>,
+    pub games_used: usize,
+    pub games_skipped: usize,
+}
+
+impl Default for BookBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl BookBuilder {
+    pub fn new() -> Self {
+        Self {
+            stats: FxHashMap::default(),
+            games_used: 0,
+            games_skipped: 0,
+        }
+    }
+
+    // Add every game of a PGN file that passes the filter, games that don't parse are skipped
+    pub fn add_pgn(&mut self, text: &str, filter: &BookFilter) {
+        for game in parse_pgn(text) {
+            let used = game.is_ok_and(|game| self.add_game(&game, filter));
+            if used { self.games_used += 1; } else { self.games_skipped += 1; }
+        }
+    }
+
+    fn add_game(&mut self, game: &Game, filter: &BookFilter) -> bool {
+        let result = game.result();
+        if !filter.results.iter().any(|accepted| accepted == result) { return false; }
+        if filter.min_elo > 0 {
+            for tag in ["WhiteElo", "BlackElo"] {
+                let elo: u32 = game.tag(tag).and_then(|elo| elo.parse().ok()).unwrap_or(0);
+                if elo < filter.min_elo { return false; }
+            }
+        }
+
+        let mut board = game.start.clone();
+        for pgn_move in game.moves.iter().take(filter.max_plies) {
+            let stats = self.stats.entry((polyglot_key(&board), encode_move(&pgn_move.mv))).or_default();
+            match (result, board.turn()) {
+                ("1/2-1/2", _) => stats.draws += 1,
+                ("1-0", Color::White) | ("0-1", Color::Black) => stats.wins += 1,
+                _ => stats.losses += 1,
+            }
+            board.play_unchecked(&pgn_move.mv);
+        }
+        true
+    }
+
+    // Polyglot book entries sorted by key and then by weight. Weights are scaled down if the busiest move
+    // would overflow the 16 bit field, and moves that never scored are dropped
+    pub fn entries(&self, filter: &BookFilter) -> Vec<(u64, u16, u16)> {
+        let kept: Vec<(&(u64, u16), &MoveStats)> = self.stats.iter()
+            .filter(|(_, stats)| stats.games() >= filter.min_games && stats.points() > 0)
+            .collect();
+        let max_points = kept.iter().map(|(_, stats)| stats.points()).max().unwrap_or(0);
+        let scale = (max_points as f64 / u16::MAX as f64).max(1.0);
+
+        let mut entries: Vec<(u64, u16, u16)> = kept.iter()
+            .map(|((key, mv), stats)| (*key, *mv, ((stats.points() as f64 / scale).round() as u16).max(1)))
+            .collect();
+        entries.sort_by(|a, b| a.0.cmp(&b.0).then(b.2.cmp(&a.2)).then(a.1.cmp(&b.1)));
+        return entries;
+    }
+
+    pub fn write(&self, path: &str, filter: &BookFilter) -> Result<usize, String> {
+        let entries = self.entries(filter);
+        let mut bytes: Vec<u8> = Vec::with_capacity(16 * entries.len());
+        for (key, mv, weight) in &entries {
+            bytes.extend_from_slice(&key.to_be_bytes());
+            bytes.extend_from_slice(&mv.to_be_bytes());
+            bytes.extend_from_slice(&weight.to_be_bytes());
+            bytes.extend_from_slice(&0u32.to_be_bytes()); // Learn field, unused
+        }
+        fs::write(path, bytes).map_err(|error| format!("could not write {}: {}", path, error))?;
+        Ok(entries.len())
+    }
+}
@@ -0,0 +1,321 @@
+use crate::params::EvalParams;
+use shakmaty::{Bitboard, Board, Chess, Color, File, Position, Rank, Role, Square, attacks};
+use std::sync::OnceLock;
+
+// Scale factors are out of SCALE_NORMAL and are applied to the endgame part of the score
+pub const SCALE_NORMAL: i32 = 64;
+pub const SCALE_DRAW: i32 = 0;
+const SCALE_OPPOSITE_BISHOPS: i32 = 22;               // Only bishops and pawns left
+const SCALE_OPPOSITE_BISHOPS_WITH_PIECES: i32 = 46;
+const SCALE_PAWNLESS_VS_MINOR: i32 = 4;               // e.g. rook against bishop
+const SCALE_PAWNLESS: i32 = 14;
+
+// Base score for an endgame that is known to be won, well clear of any normal evaluation but below mate scores
+const KNOWN_WIN: i32 = 5000;
+
+// Endgames with a dedicated evaluator, found by the material of the stronger side and the weaker side.
+// Returns the name of the endgame and the score for the side to move.
+pub fn probe_endgame(chess: &Chess, params: &EvalParams) -> Option<(&'static str, i32)> {
+    let board = chess.board();
+    if board.occupied().count() > 4 { return None; }
+
+    for strong in [Color::White, Color::Black] {
+        let (name, result) = match (material_signature(board, strong).as_str(), material_signature(board, !strong).as_str()) {
+            ("KP", "K") => ("KPK", kpk_value(chess, params, strong)),
+            ("KBN", "K") => ("KBNK", kbnk_value(board, params, strong)),
+            ("KR", "K") => ("KRK", mate_to_edge_value(board, params, strong)),
+            ("KQ", "K") => ("KQK", mate_to_edge_value(board, params, strong)),
+            ("KR", "KP") => ("KRKP", krkp_value(chess, params, strong)),
+            ("KQ", "KP") => ("KQKP", kqkp_value(board, params, strong)),
+            _ => continue,
+        };
+        return Some((name, if chess.turn() == strong { result } else { -result }));
+    }
+    None
+}
+
+// The pieces of one side, e.g. "KRP"
+fn material_signature(board: &Board, color: Color) -> String {
+    let mut signature = String::new();
+    for (role, letter) in [(Role::King, 'K'), (Role::Queen, 'Q'), (Role::Rook, 'R'), (Role::Bishop, 'B'), (Role::Knight, 'N'), (Role::Pawn, 'P')] {
+        for _ in 0..(board.by_role(role) & board.by_color(color)).count() { signature.push(letter); }
+    }
+    signature
+}
+
+fn piece_value(params: &EvalParams, role: Role) -> i32 {
+    params.piece_values_endgame[role as usize - 1]
+}
+
+// 0 in the centre up to 6 in the corners
+fn edge_distance(sq: Square) -> i32 {
+    let (file, rank) = (sq.file() as i32, sq.rank() as i32);
+    (3 - file).max(file - 4) + (3 - rank).max(rank - 4)
+}
+
+// Bonus for bringing the attacking king close to the defending one
+fn push_close(a: Square, b: Square) -> i32 {
+    140 - 20 * a.distance(b) as i32
+}
+
+// The square as seen by `color`, so that every side can be treated as White
+fn relative_square(color: Color, sq: Square) -> Square {
+    if color == Color::White { sq } else { sq.flip_vertical() }
+}
+
+// KRK and KQK - drive the king to the edge, bringing our own king up in support
+fn mate_to_edge_value(board: &Board, params: &EvalParams, strong: Color) -> i32 {
+    let (Some(winner), Some(loser)) = (board.king_of(strong), board.king_of(!strong)) else { return 0; };
+    let material = if board.queens().any() { piece_value(params, Role::Queen) } else { piece_value(params, Role::Rook) };
+    KNOWN_WIN + material + 15 * edge_distance(loser) + push_close(winner, loser)
+}
+
+// KBNK - mate is only possible in a corner the bishop controls, so drive the king towards one of those
+fn kbnk_value(board: &Board, params: &EvalParams, strong: Color) -> i32 {
+    let (Some(winner), Some(loser)) = (board.king_of(strong), board.king_of(!strong)) else { return 0; };
+    let dark_bishop = (board.bishops() & Bitboard::DARK_SQUARES).any();
+    let corners = if dark_bishop { [Square::A1, Square::H8] } else { [Square::A8, Square::H1] };
+    let corner_distance = corners.iter().map(|corner| loser.distance(*corner) as i32).min().unwrap_or(0);
+    KNOWN_WIN + piece_value(params, Role::Bishop) + piece_value(params, Role::Knight) + 30 * (7 - corner_distance) + push_close(winner, loser)
+}
+
+// KPK - exact result from the bitbase, a won pawn is worth more the further it has advanced
+fn kpk_value(chess: &Chess, params: &EvalParams, strong: Color) -> i32 {
+    let board = chess.board();
+    let (Some(winner), Some(loser), Some(pawn)) = (board.king_of(strong), board.king_of(!strong), board.pawns().first()) else { return 0; };
+    let (mut winner, mut loser, mut pawn) = (relative_square(strong, winner), relative_square(strong, loser), relative_square(strong, pawn));
+    // The bitbase only holds pawns on the a-d files
+    if pawn.file() > File::D {
+        (winner, loser, pawn) = (winner.flip_horizontal(), loser.flip_horizontal(), pawn.flip_horizontal());
+    }
+    if !kpk_bitbase()[kpk_index(chess.turn() == strong, winner, loser, pawn)] { return 0; }
+    KNOWN_WIN + piece_value(params, Role::Pawn) + 10 * pawn.rank() as i32
+}
+
+// KRKP - usually a win unless the pawn is far advanced and escorted by its king
+fn krkp_value(chess: &Chess, params: &EvalParams, strong: Color) -> i32 {
+    let board = chess.board();
+    let (Some(winner), Some(loser), Some(rook), Some(pawn)) = (board.king_of(strong), board.king_of(!strong), board.rooks().first(), board.pawns().first()) else { return 0; };
+    // Seen from the rook's side the pawn runs down the board
+    let (winner, loser, rook, pawn) = (relative_square(strong, winner), relative_square(strong, loser), relative_square(strong, rook), relative_square(strong, pawn));
+    let queening_square = Square::from_coords(pawn.file(), Rank::First);
+    let Some(pawn_stop) = pawn.offset(-8) else { return piece_value(params, Role::Rook); };
+    let weak_to_move = chess.turn() != strong;
+
+    if winner.file() == pawn.file() && winner.rank() < pawn.rank() {
+        // Our king is in front of the pawn
+        piece_value(params, Role::Rook) - winner.distance(pawn) as i32
+    } else if loser.distance(pawn) >= 3 + weak_to_move as u32 && loser.distance(rook) >= 3 {
+        // Their king is too far away to help the pawn or harass the rook
+        piece_value(params, Role::Rook) - winner.distance(pawn) as i32
+    } else if loser.rank() <= Rank::Third && loser.distance(pawn) == 1 && winner.rank() >= Rank::Fourth && winner.distance(pawn) > 2 + !weak_to_move as u32 {
+        // A far advanced pawn supported by its king, and our king can't get back in time
+        80 - 8 * winner.distance(pawn) as i32
+    } else {
+        200 - 8 * (winner.distance(pawn_stop) as i32 - loser.distance(pawn_stop) as i32 - pawn.distance(queening_square) as i32)
+    }
+}
+
+// KQKP - a win, except against a bishop or rook pawn on the 7th rank supported by its king
+fn kqkp_value(board: &Board, params: &EvalParams, strong: Color) -> i32 {
+    let (Some(winner), Some(loser), Some(pawn)) = (board.king_of(strong), board.king_of(!strong), board.pawns().first()) else { return 0; };
+    let mut result = push_close(winner, loser);
+    let drawing_files = Bitboard::from_file(File::A) | Bitboard::from_file(File::C) | Bitboard::from_file(File::F) | Bitboard::from_file(File::H);
+    let seventh = relative_square(!strong, pawn).rank() == Rank::Seventh;
+    if !seventh || loser.distance(pawn) != 1 || !drawing_files.contains(pawn) {
+        result += piece_value(params, Role::Queen) - piece_value(params, Role::Pawn);
+    }
+    result
+}
+
+// The KPK bitbase covers every position with White to play or Black to play, the pawn on the a-d files
+// and ranks 2-7, and either king anywhere. It is generated on first use by retrograde analysis.
+const KPK_SIZE: usize = 2 * 24 * 64 * 64;
+
+#[derive(Copy, Clone, PartialEq, Eq)]
+enum KpkResult {
+    Invalid,
+    Unknown,
+    Draw,
+    Win,
+}
+
+fn kpk_index(white_to_move: bool, white_king: Square, black_king: Square, pawn: Square) -> usize {
+    let pawn_index = pawn.file() as usize * 6 + (pawn.rank() as usize - 1);
+    ((pawn_index * 2 + white_to_move as usize) * 64 + white_king as usize) * 64 + black_king as usize
+}
+
+// Whether White (with the pawn) wins each position
+fn kpk_bitbase() -> &'static Vec<bool> {
+    static BITBASE: OnceLock<Vec<bool>> = OnceLock::new();
+    BITBASE.get_or_init(generate_kpk)
+}
+
+fn generate_kpk() -> Vec<bool> {
+    let mut results = vec![KpkResult::Invalid; KPK_SIZE];
+    let mut unknown = Vec::new();
+    for file in 0..4 {
+        for rank in 1..7 {
+            let pawn = Square::from_coords(File::new(file), Rank::new(rank));
+            for white_king in Square::ALL {
+                for black_king in Square::ALL {
+                    for white_to_move in [true, false] {
+                        let index = kpk_index(white_to_move, white_king, black_king, pawn);
+                        results[index] = kpk_initial(white_to_move, white_king, black_king, pawn);
+                        if results[index] == KpkResult::Unknown { unknown.push((index, white_to_move, white_king, black_king, pawn)); }
+                    }
+                }
+            }
+        }
+    }
+
+    // Keep resolving positions from their successors until nothing changes, whatever is left is a draw
+    loop {
+        let before = unknown.len();
+        unknown.retain(|&(index, white_to_move, white_king, black_king, pawn)| {
+            results[index] = kpk_classify(&results, white_to_move, white_king, black_king, pawn);
+            results[index] == KpkResult::Unknown
+        });
+        if unknown.len() == before { break; }
+    }
+    results.into_iter().map(|result| result == KpkResult::Win).collect()
+}
+
+// Positions that can be decided without looking at any moves
+fn kpk_initial(white_to_move: bool, white_king: Square, black_king: Square, pawn: Square) -> KpkResult {
+    if white_king.distance(black_king) <= 1 || white_king == pawn || black_king == pawn { return KpkResult::Invalid; }
+    let pawn_attacks = attacks::pawn_attacks(Color::White, pawn);
+    if white_to_move && pawn_attacks.contains(black_king) { return KpkResult::Invalid; }
+
+    if white_to_move && pawn.rank() == Rank::Seventh {
+        // The pawn promotes safely if the queening square is free and can't be taken
+        let Some(queening_square) = pawn.offset(8) else { return KpkResult::Unknown; };
+        if queening_square != white_king && queening_square != black_king
+            && (black_king.distance(queening_square) > 1 || white_king.distance(queening_square) == 1) {
+            return KpkResult::Win;
+        }
+    }
+    if !white_to_move {
+        let escapes = attacks::king_attacks(black_king) & !(attacks::king_attacks(white_king) | pawn_attacks);
+        if escapes.is_empty() {
+            return if pawn_attacks.contains(black_king) { KpkResult::Win } else { KpkResult::Draw };
+        }
+        // The pawn can be captured
+        if attacks::king_attacks(black_king).contains(pawn) && !attacks::king_attacks(white_king).contains(pawn) { return KpkResult::Draw; }
+    }
+    KpkResult::Unknown
+}
+
+fn kpk_classify(results: &[KpkResult], white_to_move: bool, white_king: Square, black_king: Square, pawn: Square) -> KpkResult {
+    let mut any_unknown = false;
+    if white_to_move {
+        // White wins if any move wins
+        let mut successors = Vec::new();
+        for to in attacks::king_attacks(white_king) & !attacks::king_attacks(black_king) {
+            if to != pawn { successors.push(kpk_index(false, to, black_king, pawn)); }
+        }
+        if pawn.rank() < Rank::Seventh {
+            if let Some(push) = pawn.offset(8) {
+                if push != white_king && push != black_king {
+                    successors.push(kpk_index(false, white_king, black_king, push));
+                    if pawn.rank() == Rank::Second {
+                        if let Some(double) = push.offset(8) {
+                            if double != white_king && double != black_king { successors.push(kpk_index(false, white_king, black_king, double)); }
+                        }
+                    }
+                }
+            }
+        }
+        for successor in successors {
+            match results[successor] {
+                KpkResult::Win => return KpkResult::Win,
+                KpkResult::Unknown => any_unknown = true,
+                _ => (),
+            }
+        }
+        if any_unknown { KpkResult::Unknown } else { KpkResult::Draw }
+    } else {
+        // Black draws if any move draws
+        let escapes = attacks::king_attacks(black_king) & !(attacks::king_attacks(white_king) | attacks::pawn_attacks(Color::White, pawn));
+        for to in escapes {
+            if to == pawn { return KpkResult::Draw; }
+            match results[kpk_index(true, white_king, to, pawn)] {
+                KpkResult::Draw => return KpkResult::Draw,
+                KpkResult::Unknown => any_unknown = true,
+                _ => (),
+            }
+        }
+        if any_unknown { KpkResult::Unknown } else { KpkResult::Win }
+    }
+}
+
+// How much of the endgame score `strong` (the side the evaluation favours) can expect to convert
+pub fn scale_factor(board: &Board, params: &EvalParams, strong: Color) -> i32 {
+    let weak = !strong;
+    if is_dead_draw(board) { return SCALE_DRAW; }
+
+    // Without pawns, an edge of a minor piece or less is rarely enough to win
+    let bishop_value = params.piece_values_endgame[Role::Bishop as usize - 1];
+    let rook_value = params.piece_values_endgame[Role::Rook as usize - 1];
+    let (strong_material, weak_material) = (non_pawn_material(board, params, strong), non_pawn_material(board, params, weak));
+    if (board.pawns() & board.by_color(strong)).is_empty() && strong_material - weak_material <= bishop_value {
+        if strong_material < rook_value { return SCALE_DRAW; }
+        return if weak_material <= bishop_value { SCALE_PAWNLESS_VS_MINOR } else { SCALE_PAWNLESS };
+    }
+
+    if is_wrong_rook_pawn(board, strong) { return SCALE_DRAW; }
+
+    if has_opposite_bishops(board) {
+        let only_bishops = (board.knights() | board.rooks() | board.queens()).is_empty();
+        return if only_bishops { SCALE_OPPOSITE_BISHOPS } else { SCALE_OPPOSITE_BISHOPS_WITH_PIECES };
+    }
+    SCALE_NORMAL
+}
+
+// Positions no sequence of moves can win: KK, KBK, KNK and KNNK
+pub fn is_dead_draw(board: &Board) -> bool {
+    if (board.pawns() | board.rooks() | board.queens()).any() { return false; }
+    let minors = board.knights() | board.bishops();
+    match minors.count() {
+        0 | 1 => true,
+        // Two knights can't force mate against a bare king
+        2 => board.bishops().is_empty() && ((minors & board.white()).count() == 2 || (minors & board.black()).count() == 2),
+        _ => false,
+    }
+}
+
+fn non_pawn_material(board: &Board, params: &EvalParams, color: Color) -> i32 {
+    [Role::Knight, Role::Bishop, Role::Rook, Role::Queen]
+        .into_iter()
+        .map(|role| (board.by_role(role) & board.by_color(color)).count() as i32 * params.piece_values_endgame[role as usize - 1])
+        .sum()
+}
+
+fn has_opposite_bishops(board: &Board) -> bool {
+    let (white, black) = (board.bishops() & board.white(), board.bishops() & board.black());
+    if white.count() != 1 || black.count() != 1 { return false; }
+    (white & Bitboard::DARK_SQUARES).any() != (black & Bitboard::DARK_SQUARES).any()
+}
+
+// Only rook pawns (and at most a bishop that doesn't control the queening square), with the defending king in the corner
+fn is_wrong_rook_pawn(board: &Board, strong: Color) -> bool {
+    let pawns = board.pawns() & board.by_color(strong);
+    let pieces = board.by_color(strong) & !board.pawns() & !board.kings();
+    if pawns.is_empty() || pieces.count() > 1 || (pieces & !board.bishops()).any() { return false; }
+
+    let file = if (pawns & !Bitboard::from_file(File::A)).is_empty() {
+        File::A
+    } else if (pawns & !Bitboard::from_file(File::H)).is_empty() {
+        File::H
+    } else {
+        return false;
+    };
+    let queening_square = Square::from_coords(file, if strong == Color::White { Rank::Eighth } else { Rank::First });
+    if let Some(bishop) = pieces.first() {
+        if bishop.is_dark() == queening_square.is_dark() { return false; }
+    }
+    match board.king_of(!strong) {
+        Some(king) => king.distance(queening_square) <= 1,
+        None => false,
+    }
+}
@@ -0,0 +1,112 @@
+use std::fs;
+
+use shakmaty::{Chess, Move, CastlingMode, fen::Fen, san::SanPlus};
+
+// A test position with the opcodes the runner understands
+pub struct EpdPosition {
+    pub board: Chess,
+    pub id: String,
+    pub best_moves: Vec<Move>,          // bm - any of these passes
+    pub avoid_moves: Vec<Move>,         // am - any of these fails
+    pub move_points: Vec<(Move, u32)>,  // c0 of the Strategic Test Suite, such as "f5=10, Be5+=2"
+}
+
+impl EpdPosition {
+    // Points scored by a move under the STS scheme, where only the listed moves score
+    pub fn points(&self, mv: &Move) -> u32 {
+        self.move_points.iter().find(|(scored, _)| scored == mv).map_or(0, |(_, points)| *points)
+    }
+
+    pub fn max_points(&self) -> u32 {
+        self.move_points.iter().map(|(_, points)| *points).max().unwrap_or(0)
+    }
+
+    // A move passes if it is a best move, or avoids all the moves to avoid. Positions with only STS points need the top move
+    pub fn passes(&self, mv: &Move) -> bool {
+        if self.avoid_moves.contains(mv) { return false; }
+        if !self.best_moves.is_empty() { return self.best_moves.contains(mv); }
+        if !self.avoid_moves.is_empty() { return true; }
+        self.max_points() > 0 && self.points(mv) == self.max_points()
+    }
+}
+
+pub fn load_epd(path: &str) -> Result<Vec<EpdPosition>, String> {
+    let text = fs::read_to_string(path).map_err(|error| format!("could not read {}: {}", path, error))?;
+    let mut positions = Vec::new();
+    for (line_number, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') { continue; }
+        let position = parse_epd(line).map_err(|error| format!("line {}: {}", line_number + 1, error))?;
+        positions.push(position);
+    }
+    Ok(positions)
+}
+
+// An EPD record is the first four FEN fields followed by "opcode operands;" operations
+pub fn parse_epd(line: &str) -> Result<EpdPosition, String> {
+    // Fields may be separated by any run of spaces or tabs, the operations are what follows the fourth
+    let mut fields: Vec<&str> = Vec::new();
+    let mut operations = line.trim();
+    while fields.len() < 4 && !operations.is_empty() {
+        let (field, rest) = operations.split_once(char::is_whitespace).unwrap_or((operations, ""));
+        fields.push(field);
+        operations = rest.trim_start();
+    }
+    if fields.len() < 4 { return Err("missing FEN fields".to_string()); }
+    let fen: Fen = format!("{} 0 1", fields[..4].join(" ")).parse().map_err(|_| "invalid FEN".to_string())?;
+    let board: Chess = fen.into_position(CastlingMode::Standard).map_err(|_| "illegal position".to_string())?;
+
+    let mut position = EpdPosition { board, id: String::new(), best_moves: Vec::new(), avoid_moves: Vec::new(), move_points: Vec::new() };
+    for operation in split_operations(operations) {
+        let Some((opcode, operands)) = operation.split_once(char::is_whitespace) else { continue; };
+        let operands = operands.trim();
+        match opcode {
+            "id" => position.id = operands.trim_matches('"').to_string(),
+            "bm" => position.best_moves = parse_moves(&position.board, operands.split_whitespace())?,
+            "am" => position.avoid_moves = parse_moves(&position.board, operands.split_whitespace())?,
+            "c0" => position.move_points = parse_points(&position.board, operands.trim_matches('"')),
+            _ => {}
+        }
+    }
+    if position.best_moves.is_empty() && position.avoid_moves.is_empty() && position.move_points.is_empty() {
+        return Err("no bm, am or STS c0 operation".to_string());
+    }
+    Ok(position)
+}
+
+// Operations end with ";", which may also appear inside quoted strings
+fn split_operations(text: &str) -> Vec<String> {
+    let mut operations = Vec::new();
+    let mut current = String::new();
+    let mut quoted = false;
+    for c in text.chars() {
+        match c {
+            '"' => { quoted = !quoted; current.push(c); }
+            ';' if !quoted => operations.push(current.trim().to_string()),
+            _ => current.push(c),
+        }
+        if c == ';' && !quoted { current.clear(); }
+    }
+    if !current.trim().is_empty() { operations.push(current.trim().to_string()); }
+    operations
+}
+
+fn parse_moves<'a>(board: &Chess, sans: impl Iterator<Item = &'a str>) -> Result<Vec<Move>, String> {
+    sans.map(|san| parse_san(board, san).ok_or_else(|| format!("illegal move {}", san))).collect()
+}
+
+fn parse_san(board: &Chess, san: &str) -> Option<Move> {
+    san.trim_end_matches(['!', '?']).parse::<SanPlus>().ok()?.san.to_move(board).ok()
+}
+
+// STS lists the scoring moves as "move=points" pairs. Other suites use c0 for plain comments, which score nothing
+fn parse_points(board: &Chess, text: &str) -> Vec<(Move, u32)> {
+    let mut points = Vec::new();
+    for pair in text.split(',') {
+        // Promotions such as "e8=Q=10" contain an "=" of their own, so the points follow the last one
+        let Some((san, value)) = pair.trim().rsplit_once('=') else { return Vec::new(); };
+        let (Some(mv), Ok(value)) = (parse_san(board, san.trim()), value.trim().parse::<u32>()) else { return Vec::new(); };
+        points.push((mv, value));
+    }
+    points
+}
@@ -1,4 +1,57 @@
-use shakmaty::{Chess, Position, Outcome, Color, Square, Piece, Role, Bitboard, File, Rank};
+use crate::endgame::{probe_endgame, scale_factor, SCALE_NORMAL, SCALE_DRAW};
+use crate::params::EvalParams;
+use crate::pawns::{PawnTable, PawnEntry, PAWN_TERMS, evaluate_pawns, relative_rank, ranks_ahead, neighbour_files};
+use shakmaty::{Chess, Position, Outcome, Color, Square, Piece, Role, Bitboard, File, Rank, attacks};
+use std::ops::{Add, AddAssign, Mul, Neg, Sub, SubAssign};
+
+// A pair of middlegame and endgame values, blended by the game phase
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Score {
+    pub mg: i32,
+    pub eg: i32,
+}
+
+impl Score {
+    pub const fn new(mg: i32, eg: i32) -> Self {
+        Self { mg, eg }
+    }
+
+    // Interpolate between the middlegame and endgame values
+    pub fn taper(self, phase: i32) -> i32 {
+        (self.mg * phase + self.eg * (MAX_PHASE - phase)) / MAX_PHASE
+    }
+}
+
+impl Add for Score {
+    type Output = Score;
+    fn add(self, other: Score) -> Score { Score::new(self.mg + other.mg, self.eg + other.eg) }
+}
+
+impl Sub for Score {
+    type Output = Score;
+    fn sub(self, other: Score) -> Score { Score::new(self.mg - other.mg, self.eg - other.eg) }
+}
+
+impl Neg for Score {
+    type Output = Score;
+    fn neg(self) -> Score { Score::new(-self.mg, -self.eg) }
+}
+
+impl Mul<i32> for Score {
+    type Output = Score;
+    fn mul(self, factor: i32) -> Score { Score::new(self.mg * factor, self.eg * factor) }
+}
+
+impl AddAssign for Score {
+    fn add_assign(&mut self, other: Score) { *self = *self + other; }
+}
+
+impl SubAssign for Score {
+    fn sub_assign(&mut self, other: Score) { *self = *self - other; }
+}
+
+// Game phase weights for the non-pawn pieces, the full starting material adds up to MAX_PHASE
+const MAX_PHASE: i32 = 24;
 
 pub fn evaluate_outcome(outcome: Outcome, turn: Color, ply: &usize) -> i32 {
     match outcome {
@@ -14,219 +67,153 @@ pub fn evaluate_outcome(outcome: Outcome, turn: Color, ply: &usize) -> i32 {
     }
 }
 
-pub fn evaluate_position(board: &Chess) -> i32 {
+pub fn evaluate_position(board: &Chess, params: &EvalParams) -> i32 {
     // Always returns the score from the perspective of the player to play (White by convention)
-    let mut white_material: i32 = 0;
-    let mut black_material: i32 = 0;
+    if let Some((_, score)) = probe_endgame(board, params) { return score; }
+    let mut total = Score::default();
+    evaluate_terms(board, params, evaluate_pawns(board.board(), params), |_, player, opponent| total += player - opponent);
+    return final_score(board, total, endgame_scale(board, params, total));
+}
+
+// As evaluate_position, with the pawn structure looked up in (and saved to) a pawn hash table
+pub fn evaluate_position_cached(board: &Chess, params: &EvalParams, pawn_table: &mut PawnTable) -> i32 {
+    if let Some((_, score)) = probe_endgame(board, params) { return score; }
+    let mut total = Score::default();
+    evaluate_terms(board, params, pawn_table.probe(board.board(), params), |_, player, opponent| total += player - opponent);
+    return final_score(board, total, endgame_scale(board, params, total));
+}
+
+// The contribution of every evaluation term, for the eval command
+pub struct EvalTrace {
+    pub terms: Vec<(&'static str, Score, Score)>, // (name, side to move, opponent)
+    pub phase: i32,
+    pub scale: i32, // Endgame scale factor, out of SCALE_NORMAL
+    pub score: i32, // Final score for the side to move
+    pub endgame: Option<&'static str>, // Specialised evaluator that replaced the terms, if any
+}
+
+pub fn trace_evaluation(board: &Chess, params: &EvalParams) -> EvalTrace {
+    let mut terms = Vec::new();
+    let mut total = Score::default();
+    evaluate_terms(board, params, evaluate_pawns(board.board(), params), |name, player, opponent| {
+        terms.push((name, player, opponent));
+        total += player - opponent;
+    });
+    let scale = endgame_scale(board, params, total);
+    let endgame = probe_endgame(board, params);
+    let score = endgame.map_or(final_score(board, total, scale), |(_, score)| score);
+    EvalTrace { terms, phase: game_phase(board), scale, score, endgame: endgame.map(|(name, _)| name) }
+}
+
+// Drawish endgames keep only part of their endgame score, dead draws are scored as level
+fn endgame_scale(board: &Chess, params: &EvalParams, total: Score) -> i32 {
+    let strong = if total.eg > 0 || (total.eg == 0 && total.mg >= 0) { board.turn() } else { !board.turn() };
+    scale_factor(board.board(), params, strong)
+}
+
+fn final_score(board: &Chess, total: Score, scale: i32) -> i32 {
+    if scale == SCALE_DRAW { return 0; }
+    Score::new(total.mg, total.eg * scale / SCALE_NORMAL).taper(game_phase(board))
+}
+
+// Compute every term for the side to move and the opponent and hand each pair to `record`.
+// Both the search evaluation and the trace go through here so they can never disagree.
+fn evaluate_terms(board: &Chess, params: &EvalParams, pawns: PawnEntry, mut record: impl FnMut(&'static str, Score, Score)) {
+    let mut player_material = Score::default();
+    let mut opponent_material = Score::default();
+    let mut player_squares = [Score::default(); 6];
+    let mut opponent_squares = [Score::default(); 6];
 
-    // Check if it's the endgame - if there are 4 of few top-level pieces left
-    let endgame: bool = (board.board().sliders() | board.board().knights()).count() <= 4;
-    
     // Iterate over all squares and accumulate scores
     for sq in Square::ALL {
         if let Some(piece) = board.board().piece_at(sq) {
     
-            let material_value = piece_value(piece);
-            let square_value = piece_square_value(piece, sq, endgame);
+            let material_value = piece_value(params, piece);
+            let square_value = piece_square_value(params, piece, sq);
+            let role = piece.role as usize - 1;
     
             if piece.color == board.turn() {
-                white_material += material_value + square_value;
-                if piece.role == Role::Pawn {white_material += passed_pawn_value(board, &sq, &piece.color);}
+                player_material += material_value;
+                player_squares[role] += square_value;
             } else {
-                black_material += material_value + square_value;
-                if piece.role == Role::Pawn {black_material += passed_pawn_value(board, &sq, &piece.color);}
+                opponent_material += material_value;
+                opponent_squares[role] += square_value;
             }
         }
     }
-    let material_advantage = white_material - black_material;
+    record("Material", player_material, opponent_material);
+    for (index, name) in ["PST pawns", "PST knights", "PST bishops", "PST rooks", "PST queens", "PST king"].into_iter().enumerate() {
+        record(name, player_squares[index], opponent_squares[index]);
+    }
+
+    // Pawn structure, possibly from the pawn hash table
+    let (player_pawns, opponent_pawns) = (pawns.scores.get(board.turn()), pawns.scores.get(!board.turn()));
+    for (index, name) in PAWN_TERMS.into_iter().enumerate() {
+        record(name, player_pawns[index], opponent_pawns[index]);
+    }
+    let (player, opponent) = (passer_support_value(board, params, pawns.passed, board.turn()), passer_support_value(board, params, pawns.passed, !board.turn()));
+    record("Passer support", player, opponent);
 
     // Additional terms
-    let sliding_open_file_advantage = open_file_value(board);
-    let isolated_pawn_advantage = isolated_pawn_value(board);
-    let king_protection_advantage = king_protection_value(board);
-    let bishop_pair_advantage = bishop_pair_value(board);
-    let stacked_pawn_advantage = stacked_pawn_value(board);
-    return material_advantage + 
-        sliding_open_file_advantage + 
-        isolated_pawn_advantage + 
-        king_protection_advantage + 
-        bishop_pair_advantage + 
-        stacked_pawn_advantage;
-}
-
-const PAWN_TABLE: [i32; 64] = [
-    0,  0,  0,  0,  0,  0,  0,  0,
-    50, 50, 50, 50, 50, 50, 50, 50,
-    10, 10, 20, 30, 30, 20, 10, 10,
-     5,  5, 10, 25, 25, 10,  5,  5,
-     0,  0,  0, 20, 20,  0,  0,  0,
-     5, -5,-10,  0,  0,-10, -5,  5,
-     5, 10, 10,-20,-20, 10, 10,  5,
-     0,  0,  0,  0,  0,  0,  0,  0
-];
-
-const KNIGHT_TABLE: [i32; 64] = [
-    -50,-40,-30,-30,-30,-30,-40,-50,
--40,-20,  0,  0,  0,  0,-20,-40,
--30,  0, 10, 15, 15, 10,  0,-30,
--30,  5, 15, 20, 20, 15,  5,-30,
--30,  0, 15, 20, 20, 15,  0,-30,
--30,  5, 10, 15, 15, 10,  5,-30,
--40,-20,  0,  5,  5,  0,-20,-40,
--50,-40,-30,-30,-30,-30,-40,-50
-];
-
-const BISHOP_TABLE: [i32; 64] = [
-    -20,-10,-10,-10,-10,-10,-10,-20,
--10,  0,  0,  0,  0,  0,  0,-10,
--10,  0,  5, 10, 10,  5,  0,-10,
--10,  5,  5, 10, 10,  5,  5,-10,
--10,  0, 10, 10, 10, 10,  0,-10,
--10, 10, 10, 10, 10, 10, 10,-10,
--10,  5,  0,  0,  0,  0,  5,-10,
--20,-10,-10,-10,-10,-10,-10,-20
-];
-
-const ROOK_TABLE: [i32; 64] = [
-    0,  0,  0,  0,  0,  0,  0,  0,
-    5, 10, 10, 10, 10, 10, 10,  5,
-   -5,  0,  0,  0,  0,  0,  0, -5,
-   -5,  0,  0,  0,  0,  0,  0, -5,
-   -5,  0,  0,  0,  0,  0,  0, -5,
-   -5,  0,  0,  0,  0,  0,  0, -5,
-   -5,  0,  0,  0,  0,  0,  0, -5,
-    0,  0,  0,  5,  5,  0,  0,  0
-];
-
-const QUEEN_TABLE: [i32;64] = [
-    -20,-10,-10, -5, -5,-10,-10,-20,
--10,  0,  0,  0,  0,  0,  0,-10,
--10,  0,  5,  5,  5,  5,  0,-10,
- -5,  0,  5,  5,  5,  5,  0, -5,
-  0,  0,  5,  5,  5,  5,  0, -5,
--10,  5,  5,  5,  5,  5,  0,-10,
--10,  0,  5,  0,  0,  0,  0,-10,
--20,-10,-10, -5, -5,-10,-10,-20
-];
-
-const KING_TABLE: [i32; 64] = [
-    -30,-40,-40,-50,-50,-40,-40,-30,
--30,-40,-40,-50,-50,-40,-40,-30,
--30,-40,-40,-50,-50,-40,-40,-30,
--30,-40,-40,-50,-50,-40,-40,-30,
--20,-30,-30,-40,-40,-30,-30,-20,
--10,-20,-20,-20,-20,-20,-20,-10,
- 20, 20,  0,  0,  0,  0, 20, 20,
- 20, 30, 10,  0,  0, 10, 30, 20
-];
-
-const KING_TABLE_ENDGAME: [i32; 64] = [
-    -50,-40,-30,-20,-20,-30,-40,-50,
--30,-20,-10,  0,  0,-10,-20,-30,
--30,-10, 20, 30, 30, 20,-10,-30,
--30,-10, 30, 40, 40, 30,-10,-30,
--30,-10, 30, 40, 40, 30,-10,-30,
--30,-10, 20, 30, 30, 20,-10,-30,
--30,-30,  0,  0,  0,  0,-30,-30,
--50,-30,-30,-30,-30,-30,-30,-50
-];
-
-/*
-const PAWN_TABLE: [i32; 64] = [
-    0,   0,   0,   0,   0,   0,  0,   0,
-     98, 134,  61,  95,  68, 126, 34, -11,
-     -6,   7,  26,  31,  65,  56, 25, -20,
-    -14,  13,   6,  21,  23,  12, 17, -23,
-    -27,  -2,  -5,  12,  17,   6, 10, -25,
-    -26,  -4,  -4, -10,   3,   3, 33, -12,
-    -35,  -1, -20, -23, -15,  24, 38, -22,
-      0,   0,   0,   0,   0,   0,  0,   0,];
-
-const KNIGHT_TABLE: [i32; 64] = [
-    -167, -89, -34, -49,  61, -97, -15, -107,
-     -73, -41,  72,  36,  23,  62,   7,  -17,
-     -47,  60,  37,  65,  84, 129,  73,   44,
-      -9,  17,  19,  53,  37,  69,  18,   22,
-     -13,   4,  16,  13,  28,  19,  21,   -8,
-     -23,  -9,  12,  10,  19,  17,  25,  -16,
-     -29, -53, -12,  -3,  -1,  18, -14,  -19,
-    -105, -21, -58, -33, -17, -28, -19,  -23,];
-
-const BISHOP_TABLE: [i32; 64] = [
-    -29,   4, -82, -37, -25, -42,   7,  -8,
-    -26,  16, -18, -13,  30,  59,  18, -47,
-    -16,  37,  43,  40,  35,  50,  37,  -2,
-     -4,   5,  19,  50,  37,  37,   7,  -2,
-     -6,  13,  13,  26,  34,  12,  10,   4,
-      0,  15,  15,  15,  14,  27,  18,  10,
-      4,  15,  16,   0,   7,  21,  33,   1,
-    -33,  -3, -14, -21, -13, -12, -39, -21,];
-
-const ROOK_TABLE: [i32; 64] = [
-    32,  42,  32,  51, 63,  9,  31,  43,
-     27,  32,  58,  62, 80, 67,  26,  44,
-     -5,  19,  26,  36, 17, 45,  61,  16,
-    -24, -11,   7,  26, 24, 35,  -8, -20,
-    -36, -26, -12,  -1,  9, -7,   6, -23,
-    -45, -25, -16, -17,  3,  0,  -5, -33,
-    -44, -16, -20,  -9, -1, 11,  -6, -71,
-    -19, -13,   1,  17, 16,  7, -37, -26,];
-
-const QUEEN_TABLE: [i32; 64] = [
-    -28,   0,  29,  12,  59,  44,  43,  45,
-    -24, -39,  -5,   1, -16,  57,  28,  54,
-    -13, -17,   7,   8,  29,  56,  47,  57,
-    -27, -27, -16, -16,  -1,  17,  -2,   1,
-     -9, -26,  -9, -10,  -2,  -4,   3,  -3,
-    -14,   2, -11,  -2,  -5,   2,  14,   5,
-    -35,  -8,  11,   2,   8,  15,  -3,   1,
-     -1, -18,  -9,  10, -15, -25, -31, -50,];
-
-const KING_TABLE: [i32; 64] = [
-    -65,  23,  16, -15, -56, -34,   2,  13,
-    29,  -1, -20,  -7,  -8,  -4, -38, -29,
-    -9,  24,   2, -16, -20,   6,  22, -22,
-   -17, -20, -12, -27, -30, -25, -14, -36,
-   -49,  -1, -27, -39, -46, -44, -33, -51,
-   -14, -14, -22, -46, -44, -30, -15, -27,
-     1,   7,  -8, -64, -43, -16,   9,   8,
-   -15,  36,  12, -54,   8, -28,  24,  14,];
-
-*/
-
-fn piece_square_value(piece: Piece, square: Square, endgame: bool) -> i32 {
+    let (player, opponent) = open_file_value(board, params);
+    record("Open files", player, opponent);
+    let (player, opponent) = king_protection_value(board, params);
+    record("King file safety", player, opponent);
+    let (player, opponent) = bishop_pair_value(board, params);
+    record("Bishop pair", player, opponent);
+    let (player, opponent) = (mobility_value(board, params, board.turn()), mobility_value(board, params, !board.turn()));
+    record("Mobility", player, opponent);
+    let (player, opponent) = (outpost_value(board, params, board.turn()), outpost_value(board, params, !board.turn()));
+    record("Outposts", player, opponent);
+    let (player, opponent) = (seventh_rank_value(board, params, board.turn()), seventh_rank_value(board, params, !board.turn()));
+    record("Seventh rank", player, opponent);
+    let (player, opponent) = (bad_bishop_value(board, params, board.turn()), bad_bishop_value(board, params, !board.turn()));
+    record("Bad bishops", player, opponent);
+    let (player, opponent) = (trapped_piece_value(board, params, board.turn()), trapped_piece_value(board, params, !board.turn()));
+    record("Trapped pieces", player, opponent);
+    let (player, opponent) = (connected_rooks_value(board, params, board.turn()), connected_rooks_value(board, params, !board.turn()));
+    record("Connected rooks", player, opponent);
+    let (player, opponent) = (threat_value(board, params, board.turn()), threat_value(board, params, !board.turn()));
+    for (index, name) in ["Safe pawn threats", "Lesser threats", "Hanging pieces", "Pawn push threats"].into_iter().enumerate() {
+        record(name, player[index], opponent[index]);
+    }
+    let (player, opponent) = (space_value(board, params, board.turn()), space_value(board, params, !board.turn()));
+    record("Space", player, opponent);
+    let (player, opponent) = (centre_control_value(board, params, board.turn()), centre_control_value(board, params, !board.turn()));
+    record("Centre control", player, opponent);
+    let (player_shelter, player_attacks) = king_safety_value(board, params, board.turn());
+    let (opponent_shelter, opponent_attacks) = king_safety_value(board, params, !board.turn());
+    record("King shelter", player_shelter, opponent_shelter);
+    record("King attacks", player_attacks, opponent_attacks);
+}
+
+// Remaining non-pawn material, from MAX_PHASE at the start down to 0 with only kings and pawns
+fn game_phase(board: &Chess) -> i32 {
+    let board = board.board();
+    let phase = board.knights().count() + board.bishops().count() + 2 * board.rooks().count() + 4 * board.queens().count();
+    (phase as i32).min(MAX_PHASE)
+}
 
+fn piece_square_value(params: &EvalParams, piece: Piece, square: Square) -> Score {
+
+   // Tables are written from White's point of view with a8 first, so flip the rank for White
    let index: usize = if piece.color == Color::Black {
        square as usize // Use the square index directly for black
    } else {
-       63 - (square as usize) // Mirror the index for white
+       (square as usize) ^ 56 // Mirror the rank for white
    };
 
-   match piece.role {
-       Role::Pawn => PAWN_TABLE[index],
-       Role::Knight => KNIGHT_TABLE[index],
-       Role::Bishop => BISHOP_TABLE[index], 
-       Role::Rook => ROOK_TABLE[index], 
-       Role::Queen => QUEEN_TABLE[index],  
-       Role::King => {
-        if endgame {KING_TABLE_ENDGAME[index]}
-        else {KING_TABLE[index]}
-       }
-   }
-}
-
-fn piece_value(piece: Piece) -> i32 {
-    match piece.role {
-        Role::Pawn => 100,
-        Role::Knight => 300,
-        Role::Bishop => 300,
-        Role::Rook => 500,
-        Role::Queen => 900,
-        Role::King => 0, // King has no material value
-    }
+   let role = piece.role as usize - 1;
+   Score::new(params.piece_tables[role][index], params.piece_tables_endgame[role][index])
+}
+
+fn piece_value(params: &EvalParams, piece: Piece) -> Score {
+    // King has no material value
+    let role = piece.role as usize - 1;
+    Score::new(params.piece_values[role], params.piece_values_endgame[role])
 }
 
-fn open_file_value(chess: &Chess) -> i32 {
+fn open_file_value(chess: &Chess, params: &EvalParams) -> (Score, Score) {
     let to_play: Color = chess.turn();
     let board: &shakmaty::Board = chess.board();
 
@@ -236,26 +223,27 @@ fn open_file_value(chess: &Chess) -> i32 {
     let player_pawns = board.pawns() & player_bitboard;
     let opponent_pawns = board.pawns() & opponent_bitboard;
     let player_rq = board.rooks() & player_bitboard;
-    let opponent_rq = board.rooks() & player_bitboard;
-    let (mut player_score, mut opponent_score) = (0, 0);
+    let opponent_rq = board.rooks() & opponent_bitboard;
+    let (mut player_score, mut opponent_score) = (Score::default(), Score::default());
     
     for file in File::ALL{
         let file_mask = Bitboard::from_file(file);
         // Open files
         if (file_mask & (player_pawns | opponent_pawns)).is_empty(){
-            if (file_mask & player_rq).any() { player_score += 30; }
-            if (file_mask & opponent_rq).any() { opponent_score += 30; }
+            if (file_mask & player_rq).any() { player_score += params.open_file_bonus; }
+            if (file_mask & opponent_rq).any() { opponent_score += params.open_file_bonus; }
+        }
+        // Semi-open files (only enemy pawns) for a side with a rook on the file
+        else {
+            if (file_mask & player_pawns).is_empty() && (file_mask & player_rq).any() { player_score += params.semi_open_file_bonus; }
+            if (file_mask & opponent_pawns).is_empty() && (file_mask & opponent_rq).any() { opponent_score += params.semi_open_file_bonus; }
         }
-        // Semi-open files for player with a sliding piece
-        else if (file_mask & player_pawns & !player_rq).is_empty() { player_score += 20; }
-        // Semi-open files for opponent with a sliding piece
-        else if (file_mask & opponent_pawns & !opponent_rq).is_empty() { opponent_score += 20; }
     }
-    return player_score - opponent_score;
+    return (player_score, opponent_score);
 }
 
-fn isolated_pawn_value(chess: &Chess) -> i32 {
-    let mut player_score = 0;
+fn king_protection_value(chess: &Chess, params: &EvalParams) -> (Score, Score) {
+    let (mut player_score, mut opponent_score) = (Score::default(), Score::default());
     let board: &shakmaty::Board = chess.board();
     let pawnboard = board.pawns();
 
@@ -266,102 +254,328 @@ fn isolated_pawn_value(chess: &Chess) -> i32 {
 
     for file in File::ALL{
         let file_mask = Bitboard::from_file(file);
-        let neighbour_files = get_neighbour_files(file);
+               
+        // Penalty if the king is on a file without any of its own pawns
+        if (file_mask & player_bitboard & board.kings()).any() && (file_mask & player_bitboard & pawnboard).is_empty() { player_score += params.king_open_file_penalty; }
+        if (file_mask & opponent_bitboard & board.kings()).any() && (file_mask & opponent_bitboard & pawnboard).is_empty() { opponent_score += params.king_open_file_penalty; }
 
-        // If player pawn is isolated
-        if (neighbour_files & player_bitboard & pawnboard).is_empty() & (file_mask & player_bitboard & pawnboard).any() { player_score -= 10; }
-        if (neighbour_files & opponent_bitboard & pawnboard).is_empty() & (file_mask & opponent_bitboard & pawnboard).any() { player_score += 10; }
     }
-    return player_score
+    return (player_score, opponent_score)
 }
 
-fn king_protection_value(chess: &Chess) -> i32 {
-    let mut player_score = 0;
+fn bishop_pair_value(chess: &Chess, params: &EvalParams) -> (Score, Score) {
+    let (mut player_score, mut opponent_score) = (Score::default(), Score::default());
     let board: &shakmaty::Board = chess.board();
-    let pawnboard = board.pawns();
-
-    // Player and opponent bitboard
+    let bishopboard = board.bishops();
     let to_play: Color = chess.turn();
     let player_bitboard: Bitboard = if to_play == Color::Black {board.black()} else {board.white()};
     let opponent_bitboard: Bitboard = if to_play == Color::White {board.black()} else {board.white()};
 
-    for file in File::ALL{
-        let file_mask = Bitboard::from_file(file);
-        if (file_mask & player_bitboard & board.kings()).is_empty() { continue; }
-               
-        // Penalty if the king is on an open file
-        if (file_mask & player_bitboard & pawnboard).is_empty() { player_score -= 50; }
-        if (file_mask & opponent_bitboard & pawnboard).is_empty() { player_score += 50; }
+    if (bishopboard & player_bitboard).count() >= 2 { player_score += params.bishop_pair_bonus; }
+    if (bishopboard & opponent_bitboard).count() >= 2 { opponent_score += params.bishop_pair_bonus; }
+    
+    return (player_score, opponent_score);
+}
 
+// Every square attacked by the pawns of one side
+fn pawn_attacks(board: &shakmaty::Board, color: Color) -> Bitboard {
+    let mut attacked = Bitboard::EMPTY;
+    for sq in board.pawns() & board.by_color(color) {
+        attacked |= attacks::pawn_attacks(color, sq);
     }
-    return player_score
+    return attacked;
 }
 
-fn bishop_pair_value(chess: &Chess) -> i32 {
-    let mut player_score = 0;
+fn mobility_value(chess: &Chess, params: &EvalParams, color: Color) -> Score {
     let board: &shakmaty::Board = chess.board();
-    let bishopboard = board.bishops();
-    let to_play: Color = chess.turn();
-    let player_bitboard: Bitboard = if to_play == Color::Black {board.black()} else {board.white()};
-    let opponent_bitboard: Bitboard = if to_play == Color::White {board.black()} else {board.white()};
+    let mut score = Score::default();
+
+    // Squares held by our own pieces or covered by enemy pawns don't count as useful moves
+    let mobility_area = !board.by_color(color) & !pawn_attacks(board, !color);
+
+    for sq in (board.knights() | board.bishops() | board.rooks() | board.queens()) & board.by_color(color) {
+        let count = (board.attacks_from(sq) & mobility_area).count();
+        score += match board.role_at(sq) {
+            Some(Role::Knight) => params.knight_mobility[count],
+            Some(Role::Bishop) => params.bishop_mobility[count],
+            Some(Role::Rook) => params.rook_mobility[count],
+            _ => params.queen_mobility[count],
+        };
+    }
+    return score;
+}
 
-    if (bishopboard & player_bitboard).count() == 2 { player_score += 30; }
-    if (bishopboard & opponent_bitboard).count() == 1 { player_score -= 30; }
-    
-    return player_score;
+// Knights and bishops on the 4th to 6th ranks, protected by a pawn and out of reach of enemy pawns
+fn outpost_value(chess: &Chess, params: &EvalParams, color: Color) -> Score {
+    let board: &shakmaty::Board = chess.board();
+    let own_pawns = board.pawns() & board.by_color(color);
+    let enemy_pawns = board.pawns() & board.by_color(!color);
+    let mut score = Score::default();
+
+    for sq in (board.knights() | board.bishops()) & board.by_color(color) {
+        if !(3..=5).contains(&relative_rank(color, sq)) { continue; }
+        let protected = (attacks::pawn_attacks(!color, sq) & own_pawns).any();
+        let attackable = (enemy_pawns & neighbour_files(sq.file()) & ranks_ahead(color, sq.rank())).any();
+        if protected && !attackable {
+            score += if board.knights().contains(sq) { params.knight_outpost_bonus } else { params.bishop_outpost_bonus };
+        }
+    }
+    return score;
 }
 
-fn stacked_pawn_value(chess: &Chess) -> i32 {
-    let mut player_score = 0;
+// Rooks and queens on the 7th rank, when there are pawns to attack there or the king is cut off behind
+fn seventh_rank_value(chess: &Chess, params: &EvalParams, color: Color) -> Score {
     let board: &shakmaty::Board = chess.board();
-    let pawnboard = board.pawns();
-    let to_play: Color = chess.turn();
-    let player_bitboard: Bitboard = if to_play == Color::Black {board.black()} else {board.white()};
-    let opponent_bitboard: Bitboard = if to_play == Color::White {board.black()} else {board.white()};
-    for file in File::ALL{
-        let file_mask = Bitboard::from_file(file);
-               
-        // Penalty if the king is on an open file
-        if (file_mask & player_bitboard & pawnboard).count() > 1 { player_score -= 15; }
-        if (file_mask & opponent_bitboard & pawnboard).count() > 1 { player_score += 15; }
+    let seventh = Bitboard::from_rank(if color == Color::White { Rank::Seventh } else { Rank::Second });
+    let eighth = Bitboard::from_rank(if color == Color::White { Rank::Eighth } else { Rank::First });
+    let mut score = Score::default();
+
+    if (board.pawns() & board.by_color(!color) & seventh).is_empty() && (board.kings() & board.by_color(!color) & eighth).is_empty() {
+        return score;
     }
-    return player_score
+    score += params.rook_on_seventh_bonus * (board.rooks() & board.by_color(color) & seventh).count() as i32;
+    score += params.queen_on_seventh_bonus * (board.queens() & board.by_color(color) & seventh).count() as i32;
+    return score;
 }
 
-fn get_neighbour_files(file: File) -> Bitboard {
-    let neighbour_files: Bitboard;
-    
-    if file == File::A {neighbour_files = Bitboard::from_file(File::B);}
-    else if file == File::H {neighbour_files = Bitboard::from_file(File::G);}
-    else {
-        let file_mask = Bitboard::from_file(file);
-        neighbour_files = file_mask.shift(-1) | file_mask.shift(1);
+// Own pawns fixed on the same square colour as a bishop restrict it
+fn bad_bishop_value(chess: &Chess, params: &EvalParams, color: Color) -> Score {
+    let board: &shakmaty::Board = chess.board();
+    let own_pawns = board.pawns() & board.by_color(color);
+    let mut score = Score::default();
+
+    for sq in board.bishops() & board.by_color(color) {
+        let same_colour = if sq.is_dark() { Bitboard::DARK_SQUARES } else { Bitboard::LIGHT_SQUARES };
+        score += params.bad_bishop_penalty * (own_pawns & same_colour).count() as i32;
     }
-    return neighbour_files
+    return score;
 }
 
+// A bishop shut in on a7/h7 (or a6/h6) by an enemy pawn, and a rook boxed in by its own uncastled king
+fn trapped_piece_value(chess: &Chess, params: &EvalParams, color: Color) -> Score {
+    let board: &shakmaty::Board = chess.board();
+    let enemy_pawns = board.pawns() & board.by_color(!color);
+    let mut score = Score::default();
+
+    for sq in board.bishops() & board.by_color(color) & (Bitboard::from_file(File::A) | Bitboard::from_file(File::H)) {
+        let rank = relative_rank(color, sq);
+        if rank != 5 && rank != 6 { continue; }
+        // The pawn sits diagonally in front of the bishop, towards the centre and back towards our side
+        let file = if sq.file() == File::A { File::B } else { File::G };
+        let pawn_rank = if color == Color::White { rank - 1 } else { 8 - rank };
+        if enemy_pawns.contains(Square::from_coords(file, Rank::new(pawn_rank as u32))) { score += params.trapped_bishop_penalty; }
+    }
+
+    if let Some(king) = board.king_of(color) {
+        if relative_rank(color, king) == 0 && !chess.castles().has_color(color) {
+            for sq in board.rooks() & board.by_color(color) {
+                if relative_rank(color, sq) != 0 { continue; }
+                let cornered = (king.file() >= File::E && sq.file() > king.file()) || (king.file() <= File::D && sq.file() < king.file());
+                let mobility = (board.attacks_from(sq) & !board.by_color(color)).count();
+                if cornered && mobility <= 3 { score += params.trapped_rook_penalty; }
+            }
+        }
+    }
+    return score;
+}
 
-fn passed_pawn_value(chess: &Chess, square: &Square, color: &Color) -> i32 {
-    let (r, f) = (square.rank(), square.file());
+// Two rooks defending each other along a rank or file
+fn connected_rooks_value(chess: &Chess, params: &EvalParams, color: Color) -> Score {
     let board: &shakmaty::Board = chess.board();
+    let rooks = board.rooks() & board.by_color(color);
+    if rooks.into_iter().any(|sq| (board.attacks_from(sq) & rooks).any()) { params.connected_rooks_bonus } else { Score::default() }
+}
 
-    let mut blocking_ranks = Bitboard::EMPTY;
-    if color == &Color::White {
-        for ahead in (r as u32+1)..8 {
-            blocking_ranks |= Bitboard::from_rank(Rank::new(ahead));
+// Threats made by `color` against the enemy pieces: attacks by safe pawns, attacks by less valuable pieces,
+// undefended pieces under attack and pawn pushes that would attack a piece
+fn threat_value(chess: &Chess, params: &EvalParams, color: Color) -> [Score; 4] {
+    let board: &shakmaty::Board = chess.board();
+    let own_pawns = board.pawns() & board.by_color(color);
+    let enemy_pieces = board.by_color(!color) & !board.pawns() & !board.kings();
+    let our_attacks = attacked_by(board, color);
+    let their_attacks = attacked_by(board, !color);
+    let mut scores = [Score::default(); 4];
+
+    // Pawns that can't simply be taken, and the pieces they hit
+    let mut pawn_targets = Bitboard::EMPTY;
+    for sq in own_pawns {
+        if !their_attacks.contains(sq) || our_attacks.contains(sq) { pawn_targets |= attacks::pawn_attacks(color, sq); }
+    }
+    scores[0] = params.safe_pawn_threat * (pawn_targets & enemy_pieces).count() as i32;
+
+    for sq in enemy_pieces & our_attacks {
+        let Some(victim) = board.role_at(sq) else { continue; };
+        // The least valuable of our pieces attacking this square, pawn threats are scored above
+        let attackers = board.attacks_to(sq, color, board.occupied()) & !board.pawns() & !board.kings();
+        let cheapest = attackers.into_iter().filter_map(|attacker| board.role_at(attacker)).map(|role| params.piece_values[role as usize - 1]).min();
+        if let Some(value) = cheapest {
+            if value < params.piece_values[victim as usize - 1] { scores[1] += params.threat_by_lesser[victim as usize - 1]; }
         }
+        if !their_attacks.contains(sq) { scores[2] += params.hanging_piece_bonus; }
+    }
+
+    // Squares our pawns can advance to without being lost straight away
+    let empty = !board.occupied();
+    let mut pushes = Bitboard::EMPTY;
+    for sq in own_pawns {
+        let Some(single) = sq.offset(if color == Color::White { 8 } else { -8 }) else { continue; };
+        if !empty.contains(single) { continue; }
+        pushes |= Bitboard::from(single);
+        if relative_rank(color, sq) == 1 {
+            if let Some(double) = single.offset(if color == Color::White { 8 } else { -8 }) {
+                if empty.contains(double) { pushes |= Bitboard::from(double); }
+            }
+        }
+    }
+    let mut push_targets = Bitboard::EMPTY;
+    for sq in pushes & !pawn_attacks(board, !color) & (!their_attacks | our_attacks) {
+        push_targets |= attacks::pawn_attacks(color, sq);
+    }
+    scores[3] = params.pawn_push_threat * (push_targets & enemy_pieces & !pawn_targets).count() as i32;
+    return scores;
+}
+
+// Safe squares on the central files of our half, counting double when sheltered behind our pawns.
+// Space matters more with many pieces on the board, so the count is scaled by our piece count.
+fn space_value(chess: &Chess, params: &EvalParams, color: Color) -> Score {
+    let board: &shakmaty::Board = chess.board();
+    let own_pawns = board.pawns() & board.by_color(color);
+
+    let central_files = Bitboard::from_file(File::C) | Bitboard::from_file(File::D) | Bitboard::from_file(File::E) | Bitboard::from_file(File::F);
+    let own_half = if color == Color::White {
+        Bitboard::from_rank(Rank::Second) | Bitboard::from_rank(Rank::Third) | Bitboard::from_rank(Rank::Fourth)
     } else {
-        for ahead in 0..(r as u32) {
-            blocking_ranks |= Bitboard::from_rank(Rank::new(ahead));
+        Bitboard::from_rank(Rank::Seventh) | Bitboard::from_rank(Rank::Sixth) | Bitboard::from_rank(Rank::Fifth)
+    };
+    let safe = central_files & own_half & !own_pawns & !pawn_attacks(board, !color);
+
+    // Up to three squares behind each of our pawns
+    let step = if color == Color::White { -8 } else { 8 };
+    let behind = own_pawns.shift(step) | own_pawns.shift(2 * step) | own_pawns.shift(3 * step);
+
+    let count = (safe.count() + (safe & behind).count()) as i32;
+    let weight = board.by_color(color).count() as i32;
+    return params.space_bonus * (count * weight / 16);
+}
+
+// Attacks on the centre, counted once for every piece hitting each square
+fn centre_control_value(chess: &Chess, params: &EvalParams, color: Color) -> Score {
+    let board: &shakmaty::Board = chess.board();
+    let centre = Bitboard::from(Square::D4) | Bitboard::from(Square::E4) | Bitboard::from(Square::D5) | Bitboard::from(Square::E5);
+    let mut extended_centre = Bitboard::EMPTY;
+    for file in [File::C, File::D, File::E, File::F] {
+        for rank in [Rank::Third, Rank::Fourth, Rank::Fifth, Rank::Sixth] {
+            extended_centre |= Bitboard::from(Square::from_coords(file, rank));
         }
     }
+    extended_centre &= !centre;
 
-    let neighbour_files = get_neighbour_files(f);
-    let pawnboard = board.pawns();
-    let opponent_bitboard: Bitboard = if color == &Color::White {board.black()} else {board.white()};
-    let file_mask = Bitboard::from_file(f);
+    let mut score = Score::default();
+    for sq in board.by_color(color) & !board.kings() {
+        let piece_attacks = board.attacks_from(sq);
+        score += params.centre_control_bonus * (piece_attacks & centre).count() as i32;
+        score += params.extended_centre_bonus * (piece_attacks & extended_centre).count() as i32;
+    }
+    return score;
+}
+
+// Every square attacked by any piece of one side
+fn attacked_by(board: &shakmaty::Board, color: Color) -> Bitboard {
+    let mut attacked = Bitboard::EMPTY;
+    for sq in board.by_color(color) {
+        attacked |= board.attacks_from(sq);
+    }
+    return attacked;
+}
 
-    if ((file_mask | neighbour_files) & pawnboard & opponent_bitboard & blocking_ranks).is_empty() {return 20;}
+// Safety of the king of `color`: the pawn shelter in front of it and the danger from enemy pieces
+fn king_safety_value(chess: &Chess, params: &EvalParams, color: Color) -> (Score, Score) {
+    let board: &shakmaty::Board = chess.board();
+    let Some(king) = board.king_of(color) else { return (Score::default(), Score::default()); };
+    let own_pawns = board.pawns() & board.by_color(color);
+    let enemy_pawns = board.pawns() & board.by_color(!color);
+
+    // Shelter - look at the king's file and its neighbours, shifting edge kings inwards
+    let mut shelter = Score::default();
+    let centre_file = king.file().clamp(File::B, File::G);
+    for file in [centre_file.offset(-1), Some(centre_file), centre_file.offset(1)].into_iter().flatten() {
+        let shield = nearest_pawn_ahead(own_pawns, king, file, color);
+        let storm = nearest_pawn_ahead(enemy_pawns, king, file, color);
+
+        shelter += match shield {
+            Some(distance) if distance <= 3 => params.pawn_shield[distance as usize],
+            _ => params.pawn_shield[0],
+        };
+        // An enemy pawn stopped right in front of one of our shield pawns can't advance any further
+        let blocked = matches!((shield, storm), (Some(shield), Some(storm)) if storm == shield + 1);
+        if let Some(distance) = storm {
+            if distance <= 4 && !blocked { shelter += params.pawn_storm[distance as usize - 1]; }
+        }
+    }
+
+    // Attacks - count units for every enemy piece hitting the squares around the king
+    let zone = attacks::king_attacks(king) | Bitboard::from(king);
+    let defended = attacked_by(board, color);
+    let safe = !board.by_color(!color) & !defended;
+    let occupied = board.occupied();
+    let knight_checks = attacks::knight_attacks(king);
+    let bishop_checks = attacks::bishop_attacks(king, occupied);
+    let rook_checks = attacks::rook_attacks(king, occupied);
+
+    let (mut attackers, mut units) = (0, 0);
+    for sq in (board.knights() | board.bishops() | board.rooks() | board.queens()) & board.by_color(!color) {
+        let Some(role) = board.role_at(sq) else { continue; };
+        let piece_attacks = board.attacks_from(sq);
+        let role_index = role as usize - 1;
+
+        if (piece_attacks & zone).any() {
+            attackers += 1;
+            units += params.king_attack_weights[role_index] * (piece_attacks & zone).count() as i32;
+        }
+        // Squares this piece could give check from without being captured
+        let checks = match role {
+            Role::Knight => knight_checks,
+            Role::Bishop => bishop_checks,
+            Role::Rook => rook_checks,
+            _ => bishop_checks | rook_checks,
+        };
+        if (piece_attacks & checks & safe).any() { units += params.safe_check_weights[role_index]; }
+    }
 
-    return 0;
+    // A single attacker is rarely dangerous on its own
+    let mut danger = Score::default();
+    if attackers >= 2 {
+        let index = units.clamp(0, params.king_safety_table.len() as i32 - 1) as usize;
+        danger = Score::new(-params.king_safety_table[index], 0);
+    }
+    return (shelter, danger);
+}
+
+// Distance in ranks from the king to the closest pawn in front of it on a file, as seen by `color`
+fn nearest_pawn_ahead(pawns: Bitboard, king: Square, file: File, color: Color) -> Option<u32> {
+    (pawns & Bitboard::from_file(file))
+        .into_iter()
+        .filter_map(|sq| {
+            let distance = if color == Color::White { sq.rank() as i32 - king.rank() as i32 } else { king.rank() as i32 - sq.rank() as i32 };
+            if distance > 0 { Some(distance as u32) } else { None }
+        })
+        .min()
+}
+
+// Endgame bonuses for passed pawns that depend on more than the pawns: a clear path to promotion
+// and the kings' distances to the square in front. Both grow as the pawn advances.
+fn passer_support_value(chess: &Chess, params: &EvalParams, passed: Bitboard, color: Color) -> Score {
+    let board: &shakmaty::Board = chess.board();
+    let mut score = Score::default();
+
+    for sq in passed & board.by_color(color) {
+        let weight = (relative_rank(color, sq) as i32 - 2).max(0);
+        let path = Bitboard::from_file(sq.file()) & ranks_ahead(color, sq.rank());
+        if (path & board.occupied()).is_empty() { score += params.passed_free_path_bonus * weight; }
+
+        let Some(stop) = sq.offset(if color == Color::White { 8 } else { -8 }) else { continue; };
+        if let Some(king) = board.king_of(!color) { score += params.passed_enemy_king_distance * (king.distance(stop) as i32 * weight); }
+        if let Some(king) = board.king_of(color) { score += params.passed_own_king_distance * (king.distance(stop) as i32 * weight); }
+    }
+    return score;
 }
@@ -0,0 +1,18 @@
+pub mod uci;
+pub mod parsers;
+pub mod search;
+pub mod evaluate;
+pub mod tables;
+pub mod params;
+pub mod pawns;
+pub mod endgame;
+pub mod tablebase;
+pub mod book;
+pub mod bookgen;
+pub mod pgn;
+pub mod annotate;
+pub mod epd;
+pub mod transposition;
+pub mod timeman;
+pub mod options;
+pub mod tuner;
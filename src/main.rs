@@ -1,13 +1,9 @@
-mod uci;
-mod parsers;
-mod search;
-mod evaluate;
-mod transposition;
-
-use transposition::TranspositionTable;
-use uci::{go, is_ready, perft, print_info, set_position, quit};
+use chessai::transposition::TranspositionTable;
+use chessai::options::EngineOptions;
+use chessai::uci::{go, is_ready, perft, print_info, set_option, set_position, quit, stop, ponder_hit, new_game, dump_params, eval, book, SearchHandle};
 
 use std::io::{self};
+use std::sync::{Arc, Mutex};
 use shakmaty::Chess;
 
 fn main() {
@@ -15,8 +11,14 @@ fn main() {
     // Initialize a new chess board (start from the standard initial position)
     let mut board = Chess::default();
 
-    // Initialize a new transpositon table
-    let mut t_table = TranspositionTable::new();
+    // Initialize a new transpositon table, shared with the search thread
+    let t_table = Arc::new(Mutex::new(TranspositionTable::new()));
+
+    // The search currently running in the background, if any
+    let mut search: Option<SearchHandle> = None;
+
+    // Initialize the engine options with their defaults
+    let mut options = EngineOptions::default();
 
     // Start reading input from stdin
     let stdin = io::stdin();
@@ -32,10 +34,17 @@ fn main() {
 
         if input == "uci" { print_info(); }
         else if input == "isready" { is_ready(); }
-        else if input == "quit" { quit(); }
-        else if input.starts_with("perft") { perft(input, &board); }
-        else if input.starts_with("position") { board = set_position(input, board); }
-        else if input.starts_with("go") { go(input, &board, &mut t_table); }
+        else if input == "ucinewgame" { new_game(&mut search, &t_table); }
+        else if input == "quit" { stop(&mut search); quit(); }
+        else if input == "stop" { stop(&mut search); }
+        else if input == "ponderhit" { ponder_hit(&search); }
+        else if input.starts_with("perft") { stop(&mut search); perft(input, &board); }
+        else if input.starts_with("position") { stop(&mut search); board = set_position(input, board); }
+        else if input == "eval" { stop(&mut search); eval(&board, &options); }
+        else if input == "book" { stop(&mut search); book(&board, &options); }
+        else if input.starts_with("dumpparams") { dump_params(input, &options); }
+        else if input.starts_with("setoption") { stop(&mut search); set_option(input, &mut options); }
+        else if input.starts_with("go") { stop(&mut search); search = Some(go(input, &board, &t_table, &options)); }
 
     }
 
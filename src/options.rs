@@ -0,0 +1,44 @@
+use crate::params::{EvalParams, EvalProfile};
+use crate::tablebase::Tablebases;
+use crate::book::OpeningBook;
+use std::sync::Arc;
+
+// Engine settings that can be changed by the GUI through "setoption"
+#[derive(Clone)]
+pub struct EngineOptions {
+    pub move_overhead: u64, // Milliseconds reserved for communication lag
+    pub multi_pv: usize,     // Number of best root moves to search and report
+    pub ponder: bool,        // Whether the GUI lets us think on the opponent's time
+    pub eval_profile: EvalProfile, // Built-in parameters selected with EvalProfile
+    pub eval_file: String,       // Weights applied on top of the profile, empty for none
+    pub eval_params: EvalParams, // The profile with the weights of eval_file applied
+    pub deterministic_nps: u64, // If non-zero, measure time by node count at this many nodes/sec
+    pub syzygy_path: String,     // Directories holding Syzygy tables
+    pub syzygy_probe_limit: usize, // Largest number of pieces to probe the tables for
+    pub tablebase: Option<Arc<Tablebases>>, // Tables loaded from syzygy_path, if any
+    pub own_book: bool,          // Whether to play moves from the opening book
+    pub book_depth: u32,         // Last full move number to look up in the book
+    pub best_book_move: bool,    // Always play the most weighted book move instead of a random one
+    pub book: Option<Arc<OpeningBook>>, // Polyglot book loaded from BookFile, if any
+}
+
+impl Default for EngineOptions {
+    fn default() -> Self {
+        Self {
+            move_overhead: 30,
+            multi_pv: 1,
+            ponder: false,
+            eval_profile: EvalProfile::Simplified,
+            eval_file: String::new(),
+            eval_params: EvalParams::default(),
+            deterministic_nps: 0,
+            syzygy_path: String::new(),
+            syzygy_probe_limit: 7,
+            tablebase: None,
+            own_book: false,
+            book_depth: 16,
+            best_book_move: false,
+            book: None,
+        }
+    }
+}
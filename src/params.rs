@@ -0,0 +1,292 @@
+use crate::evaluate::Score;
+use crate::tables::{SIMPLIFIED_VALUES, SIMPLIFIED_VALUES_ENDGAME, SIMPLIFIED_TABLES, SIMPLIFIED_TABLES_ENDGAME,
+    PESTO_VALUES, PESTO_VALUES_ENDGAME, PESTO_TABLES, PESTO_TABLES_ENDGAME,
+    KNIGHT_MOBILITY, KNIGHT_MOBILITY_ENDGAME, BISHOP_MOBILITY, BISHOP_MOBILITY_ENDGAME,
+    ROOK_MOBILITY, ROOK_MOBILITY_ENDGAME, QUEEN_MOBILITY, QUEEN_MOBILITY_ENDGAME, KING_SAFETY_TABLE};
+
+use std::collections::HashMap;
+use std::fs;
+
+const ROLE_NAMES: [&str; 6] = ["pawn", "knight", "bishop", "rook", "queen", "king"];
+
+// Every weight used by the evaluation. Piece values and tables are indexed by role.
+#[derive(Clone)]
+pub struct EvalParams {
+    pub piece_values: [i32; 6],
+    pub piece_values_endgame: [i32; 6],
+    pub piece_tables: [[i32; 64]; 6],
+    pub piece_tables_endgame: [[i32; 64]; 6],
+    pub open_file_bonus: Score,
+    pub semi_open_file_bonus: Score,
+    pub isolated_pawn_penalty: Score,
+    pub king_open_file_penalty: Score,
+    pub bishop_pair_bonus: Score,
+    pub stacked_pawn_penalty: Score,
+    pub passed_pawn_bonus: [Score; 8], // By rank counted from the pawn's own side
+    pub backward_pawn_penalty: Score,
+    pub connected_pawn_bonus: Score,
+    pub phalanx_pawn_bonus: Score,
+    pub candidate_passer_bonus: Score,
+    // Passed pawn bonuses per rank of advancement past the third rank
+    pub passed_free_path_bonus: Score,
+    pub passed_enemy_king_distance: Score, // Per square between the enemy king and the square in front of the pawn
+    pub passed_own_king_distance: Score,   // Per square between our king and the square in front of the pawn
+    // Piece placement
+    pub knight_outpost_bonus: Score,
+    pub bishop_outpost_bonus: Score,
+    pub rook_on_seventh_bonus: Score,
+    pub queen_on_seventh_bonus: Score,
+    pub bad_bishop_penalty: Score,  // Per own pawn on the bishop's square colour
+    pub trapped_bishop_penalty: Score,
+    pub trapped_rook_penalty: Score,
+    pub connected_rooks_bonus: Score,
+    // Threats against enemy pieces
+    pub threat_by_lesser: [Score; 6], // By victim role, when attacked by a less valuable piece
+    pub safe_pawn_threat: Score,
+    pub hanging_piece_bonus: Score,
+    pub pawn_push_threat: Score,
+    // Space and centre
+    pub space_bonus: Score,             // Per safe square, scaled by piece count
+    pub centre_control_bonus: Score,    // Per attack on d4, e4, d5 or e5
+    pub extended_centre_bonus: Score,   // Per attack on the rest of c3-f6
+    // Mobility bonuses indexed by the number of safe squares attacked
+    pub knight_mobility: [Score; 9],
+    pub bishop_mobility: [Score; 14],
+    pub rook_mobility: [Score; 15],
+    pub queen_mobility: [Score; 28],
+    // King safety
+    pub pawn_shield: [Score; 4],        // Own pawn in front of the king by distance (0 = none within 3 ranks)
+    pub pawn_storm: [Score; 4],         // Unblocked enemy pawn in front of the king by distance - 1
+    pub king_attack_weights: [i32; 6],  // Attack units per king zone square hit, by attacker role
+    pub safe_check_weights: [i32; 6],   // Attack units for a safe check, by checking role
+    pub king_safety_table: [i32; 100],  // Middlegame penalty by total attack units
+}
+
+// The built-in sets of evaluation parameters that can be selected with "setoption name EvalProfile"
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum EvalProfile {
+    Simplified,
+    Pesto,
+}
+
+impl EvalProfile {
+    pub const ALL: [EvalProfile; 2] = [EvalProfile::Simplified, EvalProfile::Pesto];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            EvalProfile::Simplified => "Simplified",
+            EvalProfile::Pesto => "PeSTO",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<EvalProfile> {
+        EvalProfile::ALL.into_iter().find(|profile| profile.name().eq_ignore_ascii_case(name))
+    }
+
+    pub fn params(self) -> EvalParams {
+        let (piece_values, piece_values_endgame, piece_tables, piece_tables_endgame) = match self {
+            EvalProfile::Simplified => (SIMPLIFIED_VALUES, SIMPLIFIED_VALUES_ENDGAME, SIMPLIFIED_TABLES, SIMPLIFIED_TABLES_ENDGAME),
+            EvalProfile::Pesto => (PESTO_VALUES, PESTO_VALUES_ENDGAME, PESTO_TABLES, PESTO_TABLES_ENDGAME),
+        };
+        EvalParams {
+            piece_values,
+            piece_values_endgame,
+            piece_tables,
+            piece_tables_endgame,
+            // The additional terms are shared by both profiles
+            open_file_bonus: Score::new(30, 15),
+            semi_open_file_bonus: Score::new(20, 10),
+            isolated_pawn_penalty: Score::new(-10, -15),
+            king_open_file_penalty: Score::new(-50, 0),
+            bishop_pair_bonus: Score::new(30, 50),
+            stacked_pawn_penalty: Score::new(-15, -25),
+            passed_pawn_bonus: score_table([0, 5, 10, 15, 25, 45, 70, 0], [0, 10, 15, 25, 45, 75, 120, 0]),
+            backward_pawn_penalty: Score::new(-8, -10),
+            connected_pawn_bonus: Score::new(10, 8),
+            phalanx_pawn_bonus: Score::new(6, 6),
+            candidate_passer_bonus: Score::new(5, 15),
+            passed_free_path_bonus: Score::new(0, 6),
+            passed_enemy_king_distance: Score::new(0, 5),
+            passed_own_king_distance: Score::new(0, -3),
+            knight_outpost_bonus: Score::new(30, 20),
+            bishop_outpost_bonus: Score::new(15, 10),
+            rook_on_seventh_bonus: Score::new(20, 30),
+            queen_on_seventh_bonus: Score::new(10, 15),
+            bad_bishop_penalty: Score::new(-3, -5),
+            trapped_bishop_penalty: Score::new(-100, -80),
+            trapped_rook_penalty: Score::new(-40, -10),
+            connected_rooks_bonus: Score::new(10, 5),
+            threat_by_lesser: [Score::new(0, 0), Score::new(28, 20), Score::new(30, 24), Score::new(44, 60), Score::new(40, 80), Score::new(0, 0)],
+            safe_pawn_threat: Score::new(80, 45),
+            hanging_piece_bonus: Score::new(35, 20),
+            pawn_push_threat: Score::new(24, 20),
+            space_bonus: Score::new(2, 0),
+            centre_control_bonus: Score::new(4, 0),
+            extended_centre_bonus: Score::new(1, 0),
+            knight_mobility: score_table(KNIGHT_MOBILITY, KNIGHT_MOBILITY_ENDGAME),
+            bishop_mobility: score_table(BISHOP_MOBILITY, BISHOP_MOBILITY_ENDGAME),
+            rook_mobility: score_table(ROOK_MOBILITY, ROOK_MOBILITY_ENDGAME),
+            queen_mobility: score_table(QUEEN_MOBILITY, QUEEN_MOBILITY_ENDGAME),
+            pawn_shield: [Score::new(0, 0), Score::new(30, 0), Score::new(18, 0), Score::new(8, 0)],
+            pawn_storm: [Score::new(-5, 0), Score::new(-35, 0), Score::new(-20, 0), Score::new(-8, 0)],
+            king_attack_weights: [0, 2, 2, 3, 5, 0],
+            safe_check_weights: [0, 6, 4, 6, 8, 0],
+            king_safety_table: KING_SAFETY_TABLE,
+        }
+    }
+}
+
+impl Default for EvalParams {
+    fn default() -> Self {
+        EvalProfile::Simplified.params()
+    }
+}
+
+impl EvalParams {
+    // Named views of every weight, in file order. Both serialisation directions are built on this.
+    pub fn entries_mut(&mut self) -> Vec<(String, Vec<&mut i32>)> {
+        let mut entries: Vec<(String, Vec<&mut i32>)> = vec![
+            ("piece_values".to_string(), self.piece_values.iter_mut().collect()),
+            ("piece_values_endgame".to_string(), self.piece_values_endgame.iter_mut().collect()),
+        ];
+        for (name, table) in ROLE_NAMES.iter().zip(self.piece_tables.iter_mut()) {
+            entries.push((format!("{}_table", name), table.iter_mut().collect()));
+        }
+        for (name, table) in ROLE_NAMES.iter().zip(self.piece_tables_endgame.iter_mut()) {
+            entries.push((format!("{}_table_endgame", name), table.iter_mut().collect()));
+        }
+        let scores: [(&str, &mut Score); 27] = [
+            ("open_file_bonus", &mut self.open_file_bonus),
+            ("semi_open_file_bonus", &mut self.semi_open_file_bonus),
+            ("isolated_pawn_penalty", &mut self.isolated_pawn_penalty),
+            ("king_open_file_penalty", &mut self.king_open_file_penalty),
+            ("bishop_pair_bonus", &mut self.bishop_pair_bonus),
+            ("stacked_pawn_penalty", &mut self.stacked_pawn_penalty),
+            ("backward_pawn_penalty", &mut self.backward_pawn_penalty),
+            ("connected_pawn_bonus", &mut self.connected_pawn_bonus),
+            ("phalanx_pawn_bonus", &mut self.phalanx_pawn_bonus),
+            ("candidate_passer_bonus", &mut self.candidate_passer_bonus),
+            ("passed_free_path_bonus", &mut self.passed_free_path_bonus),
+            ("passed_enemy_king_distance", &mut self.passed_enemy_king_distance),
+            ("passed_own_king_distance", &mut self.passed_own_king_distance),
+            ("knight_outpost_bonus", &mut self.knight_outpost_bonus),
+            ("bishop_outpost_bonus", &mut self.bishop_outpost_bonus),
+            ("rook_on_seventh_bonus", &mut self.rook_on_seventh_bonus),
+            ("queen_on_seventh_bonus", &mut self.queen_on_seventh_bonus),
+            ("bad_bishop_penalty", &mut self.bad_bishop_penalty),
+            ("trapped_bishop_penalty", &mut self.trapped_bishop_penalty),
+            ("trapped_rook_penalty", &mut self.trapped_rook_penalty),
+            ("connected_rooks_bonus", &mut self.connected_rooks_bonus),
+            ("safe_pawn_threat", &mut self.safe_pawn_threat),
+            ("hanging_piece_bonus", &mut self.hanging_piece_bonus),
+            ("pawn_push_threat", &mut self.pawn_push_threat),
+            ("space_bonus", &mut self.space_bonus),
+            ("centre_control_bonus", &mut self.centre_control_bonus),
+            ("extended_centre_bonus", &mut self.extended_centre_bonus),
+        ];
+        for (name, score) in scores {
+            entries.push((name.to_string(), vec![&mut score.mg, &mut score.eg]));
+        }
+        // Score tables are written as separate middlegame and endgame arrays, like the piece tables
+        let score_tables: [(&str, &mut [Score]); 8] = [
+            ("passed_pawn_bonus", &mut self.passed_pawn_bonus),
+            ("threat_by_lesser", &mut self.threat_by_lesser),
+            ("knight_mobility", &mut self.knight_mobility),
+            ("bishop_mobility", &mut self.bishop_mobility),
+            ("rook_mobility", &mut self.rook_mobility),
+            ("queen_mobility", &mut self.queen_mobility),
+            ("pawn_shield", &mut self.pawn_shield),
+            ("pawn_storm", &mut self.pawn_storm),
+        ];
+        for (name, table) in score_tables {
+            let (mg, eg): (Vec<&mut i32>, Vec<&mut i32>) = table.iter_mut().map(|score| (&mut score.mg, &mut score.eg)).unzip();
+            entries.push((name.to_string(), mg));
+            entries.push((format!("{}_endgame", name), eg));
+        }
+        entries.push(("king_attack_weights".to_string(), self.king_attack_weights.iter_mut().collect()));
+        entries.push(("safe_check_weights".to_string(), self.safe_check_weights.iter_mut().collect()));
+        entries.push(("king_safety_table".to_string(), self.king_safety_table.iter_mut().collect()));
+        entries
+    }
+
+    // All weights flattened into a single vector, in file order (used by the tuner)
+    pub fn to_vector(&self) -> Vec<i32> {
+        let mut copy = self.clone();
+        copy.entries_mut().into_iter().flat_map(|(_, values)| values.into_iter().map(|value| *value)).collect()
+    }
+
+    pub fn set_vector(&mut self, vector: &[i32]) {
+        for (value, new_value) in self.entries_mut().into_iter().flat_map(|(_, values)| values).zip(vector) {
+            *value = *new_value;
+        }
+    }
+
+    // Write the parameters in a TOML-like format: one "name = [values]" array per weight
+    pub fn to_text(&self) -> String {
+        let mut copy = self.clone();
+        let mut text = String::from("# je_bot evaluation parameters, (mg, eg) pairs are written as [mg, eg]\n");
+        for (name, values) in copy.entries_mut() {
+            let values: Vec<String> = values.iter().map(|value| value.to_string()).collect();
+            if values.len() == 64 {
+                // Lay tables out as a board, a8 first
+                text.push_str(&format!("{} = [\n", name));
+                for rank in values.chunks(8) {
+                    text.push_str(&format!("    {},\n", rank.join(", ")));
+                }
+                text.push_str("]\n");
+            } else {
+                text.push_str(&format!("{} = [{}]\n", name, values.join(", ")));
+            }
+        }
+        text
+    }
+
+    // Parse the format written by to_text. Weights missing from the text keep their current value.
+    pub fn from_text(&self, text: &str) -> Result<EvalParams, String> {
+        let values = parse_arrays(text)?;
+        let mut params = self.clone();
+        let entries = params.entries_mut();
+        if let Some(unknown) = values.keys().find(|name| !entries.iter().any(|(entry_name, _)| entry_name == *name)) {
+            return Err(format!("unknown parameter {}", unknown));
+        }
+        for (name, entry) in entries {
+            let Some(new_values) = values.get(&name) else { continue; };
+            if new_values.len() != entry.len() {
+                return Err(format!("{} has {} values, expected {}", name, new_values.len(), entry.len()));
+            }
+            for (value, new_value) in entry.into_iter().zip(new_values) {
+                *value = *new_value;
+            }
+        }
+        Ok(params)
+    }
+
+    pub fn load_file(&self, path: &str) -> Result<EvalParams, String> {
+        let text = fs::read_to_string(path).map_err(|error| format!("could not read {}: {}", path, error))?;
+        self.from_text(&text)
+    }
+}
+
+fn score_table<const N: usize>(mg: [i32; N], eg: [i32; N]) -> [Score; N] {
+    std::array::from_fn(|index| Score::new(mg[index], eg[index]))
+}
+
+// Collect every "name = [v1, v2, ...]" array, which may span several lines
+fn parse_arrays(text: &str) -> Result<HashMap<String, Vec<i32>>, String> {
+    // Drop comments, then split on the closing brackets
+    let text: String = text.lines().map(|line| line.split('#').next().unwrap_or("")).collect::<Vec<&str>>().join("\n");
+    let mut arrays = HashMap::new();
+    for item in text.split(']') {
+        if item.trim().is_empty() { continue; }
+        let (name, values) = item.split_once('=').ok_or_else(|| format!("expected 'name = [...]' near '{}'", item.trim()))?;
+        let values = values.trim().strip_prefix('[').ok_or_else(|| format!("expected '[' after {}", name.trim()))?;
+        let values = values
+            .split(',')
+            .map(|value| value.trim())
+            .filter(|value| !value.is_empty())
+            .map(|value| value.parse::<i32>().map_err(|_| format!("invalid value '{}' in {}", value, name.trim())))
+            .collect::<Result<Vec<i32>, String>>()?;
+        arrays.insert(name.trim().to_string(), values);
+    }
+    Ok(arrays)
+}
@@ -9,38 +9,55 @@ pub fn parse_perft_command(input: &str) -> usize {
     }
 }
 
-pub fn parse_go_command(input: &str, is_white_turn: bool) -> (u64, u64) {
-    // Default time and increment values
-    let mut wtime: u64 = 0;
-    let mut btime: u64 = 0;
-    let mut winc: u64 = 0;
-    let mut binc: u64 = 0;
+pub struct GoCommand {
+    pub time: Option<u64>,      // Remaining time for the side to move
+    pub inc: u64,               // Increment for the side to move
+    pub movestogo: Option<u64>, // Moves until the next time control
+    pub nodes: Option<u64>,     // Maximum number of nodes to search
+    pub movetime: Option<u64>,  // Search exactly this many milliseconds
+    pub ponder: bool,           // Search on the opponent's time until "ponderhit" or "stop"
+    pub infinite: bool,         // Search until "stop"
+}
 
+pub fn parse_go_command(input: &str, is_white_turn: bool) -> GoCommand {
     // Create a HashMap for easy parsing of arguments
-    let mut options: HashMap<&str, u64> = HashMap::new();
+    // Clocks can go negative when the GUI has already flagged us, so values are read as signed
+    let mut options: HashMap<&str, i64> = HashMap::new();
 
     // Split the input and extract key-value pairs
     let tokens: Vec<&str> = input.split_whitespace().collect();
     let mut i: usize = 1;  // Start after the "go" token
     while i < tokens.len() {
-        if i + 1 < tokens.len() {
-            if let Ok(value) = tokens[i + 1].parse::<u64>() {
-                options.insert(tokens[i], value);
-            }
+        // Flags such as "ponder" have no value, so only consume a pair when the next token is a number
+        if let Some(Ok(value)) = tokens.get(i + 1).map(|token| token.parse::<i64>()) {
+            options.insert(tokens[i], value);
+            i += 2;
+        } else {
+            i += 1;
         }
-        i += 2;
     }
 
-    // Extract values for wtime, btime, winc, binc
-    if let Some(&val) = options.get("wtime") { wtime = val; }
-    if let Some(&val) = options.get("btime") { btime = val; }
-    if let Some(&val) = options.get("winc") { winc = val; }
-    if let Some(&val) = options.get("binc") { binc = val; }
-
     // Return the correct time and increment for the current player
-    if is_white_turn {
-        (wtime, winc)
-    } else {
-        (btime, binc)
+    let (time_key, inc_key) = if is_white_turn { ("wtime", "winc") } else { ("btime", "binc") };
+    // Negative values are clamped to 0, so an overdrawn clock gets the emergency allocation
+    let get = |key: &str| options.get(key).map(|value| (*value).max(0) as u64);
+    GoCommand {
+        time: get(time_key),
+        inc: get(inc_key).unwrap_or(0),
+        movestogo: get("movestogo"),
+        nodes: get("nodes"),
+        movetime: get("movetime"),
+        ponder: tokens.contains(&"ponder"),
+        infinite: tokens.contains(&"infinite"),
     }
-}
\ No newline at end of file
+}
+
+pub fn parse_setoption_command(input: &str) -> (String, Option<String>) {
+    // UCI command looks like: setoption name <id> [value <x>], where both may contain spaces
+    let rest = input.trim_start_matches("setoption").trim();
+    let rest = rest.strip_prefix("name").unwrap_or(rest).trim();
+    match rest.split_once(" value") {
+        Some((name, value)) => (name.trim().to_string(), Some(value.trim().to_string())),
+        None => (rest.to_string(), None),
+    }
+}
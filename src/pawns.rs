@@ -0,0 +1,138 @@
+use crate::evaluate::Score;
+use crate::params::EvalParams;
+use shakmaty::{Bitboard, Board, ByColor, Color, File, Rank, Square, attacks, zobrist::{Zobrist64, ZobristValue}};
+use rustc_hash::FxHashMap;
+
+// The pawn structure terms, in the order they are stored and recorded
+pub const PAWN_TERMS: [&str; 7] = [
+    "Isolated pawns", "Stacked pawns", "Backward pawns", "Connected pawns", "Phalanx pawns", "Candidate passers", "Passed pawns"];
+
+// The table is cleared once it holds this many entries
+const PAWN_TABLE_ENTRIES: usize = 1 << 16;
+
+// Everything that depends only on where the pawns stand
+#[derive(Copy, Clone)]
+pub struct PawnEntry {
+    pub scores: ByColor<[Score; 7]>, // One score per entry of PAWN_TERMS
+    pub passed: Bitboard,            // Passed pawns of both colours
+}
+
+// Pawn structures repeat far more often than positions, so their evaluation is cached separately
+pub struct PawnTable {
+    table: FxHashMap<Zobrist64, PawnEntry>,
+}
+
+impl Default for PawnTable {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PawnTable {
+    pub fn new() -> Self {
+        Self {
+            table: FxHashMap::default(),
+        }
+    }
+
+    pub fn probe(&mut self, board: &Board, params: &EvalParams) -> PawnEntry {
+        let key = pawn_key(board);
+        if let Some(entry) = self.table.get(&key) { return *entry; }
+
+        let entry = evaluate_pawns(board, params);
+        if self.table.len() >= PAWN_TABLE_ENTRIES { self.table.clear(); }
+        self.table.insert(key, entry);
+        entry
+    }
+}
+
+// A Zobrist key built from the pawns alone
+pub fn pawn_key(board: &Board) -> Zobrist64 {
+    let mut key = Zobrist64::default();
+    for sq in board.pawns() {
+        if let Some(piece) = board.piece_at(sq) { key ^= Zobrist64::zobrist_for_piece(sq, piece); }
+    }
+    key
+}
+
+pub fn evaluate_pawns(board: &Board, params: &EvalParams) -> PawnEntry {
+    let mut entry = PawnEntry { scores: ByColor { black: [Score::default(); 7], white: [Score::default(); 7] }, passed: Bitboard::EMPTY };
+    for color in [Color::White, Color::Black] {
+        let (scores, passed) = pawn_structure(board, params, color);
+        if color == Color::White { entry.scores.white = scores; } else { entry.scores.black = scores; }
+        entry.passed |= passed;
+    }
+    entry
+}
+
+fn pawn_structure(board: &Board, params: &EvalParams, color: Color) -> ([Score; 7], Bitboard) {
+    let own_pawns = board.pawns() & board.by_color(color);
+    let enemy_pawns = board.pawns() & board.by_color(!color);
+    let (mut isolated, mut stacked, mut backward, mut connected, mut phalanx, mut candidate, mut passed_score) =
+        (Score::default(), Score::default(), Score::default(), Score::default(), Score::default(), Score::default(), Score::default());
+    let mut passed = Bitboard::EMPTY;
+
+    // File based terms count once per file
+    for file in File::ALL {
+        let file_mask = Bitboard::from_file(file);
+        if (neighbour_files(file) & own_pawns).is_empty() && (file_mask & own_pawns).any() { isolated += params.isolated_pawn_penalty; }
+        if (file_mask & own_pawns).count() > 1 { stacked += params.stacked_pawn_penalty; }
+    }
+
+    for sq in own_pawns {
+        let file_mask = Bitboard::from_file(sq.file());
+        let neighbours = neighbour_files(sq.file());
+        let ahead = ranks_ahead(color, sq.rank());
+
+        // Own pawns beside or behind on the neighbouring files, which can still come up to support this one
+        let supporters = own_pawns & neighbours & !ahead;
+        // Enemy pawns that guard the squares in front of this one
+        let sentries = enemy_pawns & neighbours & ahead;
+
+        if ((file_mask | neighbours) & enemy_pawns & ahead).is_empty() {
+            passed |= Bitboard::from(sq);
+            passed_score += params.passed_pawn_bonus[relative_rank(color, sq)];
+        } else if (file_mask & enemy_pawns & ahead).is_empty() && supporters.count() >= sentries.count() {
+            candidate += params.candidate_passer_bonus;
+        }
+
+        if (attacks::pawn_attacks(!color, sq) & own_pawns).any() { connected += params.connected_pawn_bonus; }
+        if (own_pawns & neighbours & Bitboard::from_rank(sq.rank())).any() { phalanx += params.phalanx_pawn_bonus; }
+
+        // Backward - no pawn left that could support it, and it can't advance without being captured
+        let stop = sq.offset(if color == Color::White { 8 } else { -8 });
+        if let Some(stop) = stop {
+            if (own_pawns & neighbours).any() && supporters.is_empty() && (attacks::pawn_attacks(color, stop) & enemy_pawns).any() {
+                backward += params.backward_pawn_penalty;
+            }
+        }
+    }
+    ([isolated, stacked, backward, connected, phalanx, candidate, passed_score], passed)
+}
+
+// Rank counted from the given side, 0 is its back rank
+pub fn relative_rank(color: Color, sq: Square) -> usize {
+    if color == Color::White { sq.rank() as usize } else { 7 - sq.rank() as usize }
+}
+
+// Every rank in front of the given one, as seen by `color`
+pub fn ranks_ahead(color: Color, rank: Rank) -> Bitboard {
+    let mut ranks = Bitboard::EMPTY;
+    for other in Rank::ALL {
+        let in_front = if color == Color::White { other > rank } else { other < rank };
+        if in_front { ranks |= Bitboard::from_rank(other); }
+    }
+    ranks
+}
+
+pub fn neighbour_files(file: File) -> Bitboard {
+    let neighbour_files: Bitboard;
+
+    if file == File::A {neighbour_files = Bitboard::from_file(File::B);}
+    else if file == File::H {neighbour_files = Bitboard::from_file(File::G);}
+    else {
+        let file_mask = Bitboard::from_file(file);
+        neighbour_files = file_mask.shift(-1) | file_mask.shift(1);
+    }
+    return neighbour_files
+}
@@ -0,0 +1,377 @@
+use shakmaty::{Chess, Color, Move, Position, CastlingMode, EnPassantMode, fen::Fen, san::SanPlus};
+
+// Scores beyond this are mates, counted in plies from MATE (as in evaluate_outcome)
+const MATE: i32 = 10_000;
+const MATE_THRESHOLD: i32 = 9_000;
+
+// Longest movetext line written, as recommended by the PGN standard
+const LINE_LENGTH: usize = 80;
+
+// A move of a game with everything PGN can attach to it
+#[derive(Clone)]
+pub struct PgnMove {
+    pub mv: Move,
+    pub comment_before: Option<String>, // Comment placed before the move, usually at the start of a variation
+    pub comment: Option<String>,        // Comment after the move, without any [%eval] command
+    pub eval: Option<i32>,              // Centipawns from White's point of view, written as [%eval]
+    pub nags: Vec<u8>,                  // Numeric annotation glyphs, "!" and "?" are stored as $1 and $2 and so on
+    pub variations: Vec<Vec<PgnMove>>,  // Alternatives to this move, played from the position before it
+}
+
+impl PgnMove {
+    pub fn new(mv: Move) -> Self {
+        Self {
+            mv,
+            comment_before: None,
+            comment: None,
+            eval: None,
+            nags: Vec::new(),
+            variations: Vec::new(),
+        }
+    }
+}
+
+pub struct Game {
+    pub tags: Vec<(String, String)>, // In the order they are written
+    pub start: Chess,
+    pub moves: Vec<PgnMove>,         // The main line
+}
+
+impl Game {
+    // An empty game with the Seven Tag Roster, from a custom position if one is given
+    pub fn new(start: Chess) -> Self {
+        let mut game = Self {
+            tags: ["Event", "Site", "Date", "Round", "White", "Black"].iter().map(|name| (name.to_string(), "?".to_string())).collect(),
+            start,
+            moves: Vec::new(),
+        };
+        game.set_tag("Result", "*");
+        let fen = Fen::from_position(game.start.clone(), EnPassantMode::Legal).to_string();
+        if fen != Fen::from_position(Chess::default(), EnPassantMode::Legal).to_string() {
+            game.set_tag("SetUp", "1");
+            game.set_tag("FEN", &fen);
+        }
+        game
+    }
+
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        self.tags.iter().find(|(tag, _)| tag == name).map(|(_, value)| value.as_str())
+    }
+
+    pub fn set_tag(&mut self, name: &str, value: &str) {
+        match self.tags.iter_mut().find(|(tag, _)| tag == name) {
+            Some(tag) => tag.1 = value.to_string(),
+            None => self.tags.push((name.to_string(), value.to_string())),
+        }
+    }
+
+    pub fn result(&self) -> &str {
+        self.tag("Result").unwrap_or("*")
+    }
+
+    // The position before each move of the main line, followed by the final position
+    pub fn positions(&self) -> Vec<Chess> {
+        let mut positions = vec![self.start.clone()];
+        let mut board = self.start.clone();
+        for pgn_move in &self.moves {
+            board.play_unchecked(&pgn_move.mv);
+            positions.push(board.clone());
+        }
+        positions
+    }
+}
+
+enum Token {
+    Tag(String, String),
+    Comment(String),
+    Nag(u8),
+    Open,
+    Close,
+    Result(String),
+    San(String),
+}
+
+// Parse every game of a PGN file. A game that fails to parse gives an error and reading continues with the next one
+pub fn parse_pgn(text: &str) -> Vec<Result<Game, String>> {
+    let tokens = tokenize(text);
+    let mut games = Vec::new();
+    let mut pos = 0;
+
+    while pos < tokens.len() {
+        let game_number = games.len() + 1;
+        match parse_game(&tokens, &mut pos) {
+            Ok(game) => games.push(Ok(game)),
+            Err(error) => {
+                games.push(Err(format!("game {}: {}", game_number, error)));
+                // Skip the rest of the broken game
+                while pos < tokens.len() && !matches!(tokens[pos], Token::Tag(..)) {
+                    pos += 1;
+                    if matches!(tokens[pos - 1], Token::Result(_)) { break; }
+                }
+            }
+        }
+    }
+    games
+}
+
+fn parse_game(tokens: &[Token], pos: &mut usize) -> Result<Game, String> {
+    let mut tags: Vec<(String, String)> = Vec::new();
+    while let Some(Token::Tag(name, value)) = tokens.get(*pos) {
+        tags.push((name.clone(), value.clone()));
+        *pos += 1;
+    }
+
+    let start = match tags.iter().find(|(name, _)| name == "FEN") {
+        Some((_, fen)) => {
+            let fen: Fen = fen.parse().map_err(|_| format!("invalid FEN {}", fen))?;
+            fen.into_position(CastlingMode::Standard).map_err(|_| "illegal FEN position".to_string())?
+        }
+        None => Chess::default(),
+    };
+
+    let moves = parse_line(tokens, pos, start.clone(), 0)?;
+    // The result after the movetext wins over a missing or different Result tag
+    let mut game = Game { tags, start, moves };
+    if let Some(Token::Result(result)) = tokens.get(*pos) {
+        game.set_tag("Result", result);
+        *pos += 1;
+    }
+    if game.tag("Result").is_none() { game.set_tag("Result", "*"); }
+    Ok(game)
+}
+
+// Parse a line of moves from the given position, up to the end of its variation or the end of the game
+fn parse_line(tokens: &[Token], pos: &mut usize, mut board: Chess, depth: usize) -> Result<Vec<PgnMove>, String> {
+    let mut line: Vec<PgnMove> = Vec::new();
+    let mut before = board.clone(); // Position before the last move, where its variations start
+    let mut pending_comment: Option<String> = None;
+
+    while let Some(token) = tokens.get(*pos) {
+        match token {
+            Token::San(san) => {
+                let mv = san.parse::<SanPlus>().ok()
+                    .and_then(|san| san.san.to_move(&board).ok())
+                    .ok_or_else(|| format!("illegal move {} in {}", san, Fen::from_position(board.clone(), EnPassantMode::Legal)))?;
+                let mut pgn_move = PgnMove::new(mv);
+                pgn_move.comment_before = pending_comment.take();
+                before = board.clone();
+                board.play_unchecked(&pgn_move.mv);
+                line.push(pgn_move);
+            }
+            Token::Comment(text) => {
+                let (eval, text) = split_eval(text);
+                match line.last_mut().filter(|_| pending_comment.is_none()) {
+                    Some(last) => {
+                        if eval.is_some() { last.eval = eval; }
+                        if !text.is_empty() { last.comment = Some(join_comments(last.comment.take(), text)); }
+                    }
+                    None => if !text.is_empty() { pending_comment = Some(join_comments(pending_comment.take(), text)); },
+                }
+            }
+            Token::Nag(nag) => {
+                if let Some(last) = line.last_mut() { last.nags.push(*nag); }
+            }
+            Token::Open => {
+                *pos += 1;
+                let variation = parse_line(tokens, pos, before.clone(), depth + 1)?;
+                let last = line.last_mut().ok_or("variation before the first move")?;
+                if !variation.is_empty() { last.variations.push(variation); }
+            }
+            Token::Close => {
+                if depth == 0 { return Err("unmatched )".to_string()); }
+                break;
+            }
+            // Results and tags end the main line, stray results inside variations are ignored
+            Token::Result(_) => if depth == 0 { return Ok(line); },
+            Token::Tag(..) => if depth == 0 { return Ok(line); } else { return Err("unterminated variation".to_string()); },
+        }
+        *pos += 1;
+    }
+    if depth > 0 && *pos >= tokens.len() { return Err("unterminated variation".to_string()); }
+    Ok(line)
+}
+
+fn join_comments(existing: Option<String>, text: String) -> String {
+    match existing {
+        Some(existing) => format!("{} {}", existing, text),
+        None => text,
+    }
+}
+
+fn tokenize(text: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let chars: Vec<char> = text.chars().collect();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        match c {
+            '[' => {
+                let end = chars[i..].iter().position(|&c| c == ']').map_or(chars.len(), |end| i + end);
+                let inner: String = chars[i + 1..end].iter().collect();
+                if let Some((name, value)) = inner.trim().split_once(char::is_whitespace) {
+                    tokens.push(Token::Tag(name.to_string(), value.trim().trim_matches('"').replace("\\\"", "\"")));
+                }
+                i = end + 1;
+            }
+            '{' => {
+                let end = chars[i..].iter().position(|&c| c == '}').map_or(chars.len(), |end| i + end);
+                tokens.push(Token::Comment(chars[i + 1..end].iter().collect::<String>().split_whitespace().collect::<Vec<_>>().join(" ")));
+                i = end + 1;
+            }
+            ';' => {
+                let end = chars[i..].iter().position(|&c| c == '\n').map_or(chars.len(), |end| i + end);
+                tokens.push(Token::Comment(chars[i + 1..end].iter().collect::<String>().trim().to_string()));
+                i = end + 1;
+            }
+            // Escaped lines such as "%evalengine" are skipped
+            '%' if i == 0 || chars[i - 1] == '\n' => {
+                i = chars[i..].iter().position(|&c| c == '\n').map_or(chars.len(), |end| i + end);
+            }
+            '(' => { tokens.push(Token::Open); i += 1; }
+            ')' => { tokens.push(Token::Close); i += 1; }
+            c if c.is_whitespace() => i += 1,
+            _ => {
+                let end = chars[i..].iter().position(|&c| c.is_whitespace() || "{}()[];".contains(c)).map_or(chars.len(), |end| i + end);
+                let word: String = chars[i..end].iter().collect();
+                push_word(&mut tokens, &word);
+                i = end;
+            }
+        }
+    }
+    tokens
+}
+
+// A movetext word is a move number, a NAG, a result or a SAN move with optional "!" and "?" suffixes
+fn push_word(tokens: &mut Vec<Token>, word: &str) {
+    if let Some(nag) = word.strip_prefix('$') {
+        if let Ok(nag) = nag.parse::<u8>() { tokens.push(Token::Nag(nag)); }
+        return;
+    }
+    if matches!(word, "1-0" | "0-1" | "1/2-1/2" | "*") {
+        tokens.push(Token::Result(word.to_string()));
+        return;
+    }
+
+    // Move numbers may be glued to the move, as in "12.Nf3" or "12...Nf6"
+    let word = word.rsplit_once('.').map_or(word, |(_, rest)| rest);
+    // Older files castle with zeros, which would otherwise be taken for a move number
+    let word = match ["0-0-0", "0-0"].into_iter().find(|zeros| word.starts_with(zeros)) {
+        Some(zeros) => format!("{}{}", zeros.replace('0', "O"), &word[zeros.len()..]),
+        None => word.trim_start_matches(|c: char| c.is_ascii_digit()).to_string(),
+    };
+    let san = word.trim_end_matches(['!', '?']);
+    if !san.is_empty() { tokens.push(Token::San(san.to_string())); }
+    let suffix = &word[san.len()..];
+    let nag = match suffix {
+        "!" => 1, "?" => 2, "!!" => 3, "??" => 4, "!?" => 5, "?!" => 6,
+        _ => 0,
+    };
+    if nag > 0 { tokens.push(Token::Nag(nag)); }
+}
+
+// Pull a [%eval ...] command out of a comment, leaving the rest of the text
+fn split_eval(comment: &str) -> (Option<i32>, String) {
+    let Some(start) = comment.find("[%eval") else { return (None, comment.to_string()); };
+    let Some(length) = comment[start..].find(']') else { return (None, comment.to_string()); };
+    let value = comment[start + 6..start + length].split_whitespace().next().unwrap_or("");
+    let rest = format!("{} {}", &comment[..start], &comment[start + length + 1..]);
+    (parse_eval(value), rest.split_whitespace().collect::<Vec<_>>().join(" "))
+}
+
+// "0.35" is centipawns in pawns, "#3" and "#-3" are mates in moves for White and Black
+pub fn parse_eval(value: &str) -> Option<i32> {
+    match value.strip_prefix('#') {
+        Some(mate) => {
+            let moves: i32 = mate.parse().ok()?;
+            // The number of plies isn't known from the moves, so the mating side is assumed to be on move
+            if moves > 0 { Some(MATE - (2 * moves - 1)) } else { Some(-(MATE + 2 * moves + 1)) }
+        }
+        None => value.parse::<f64>().ok().map(|pawns| (pawns * 100.0).round() as i32),
+    }
+}
+
+pub fn format_eval(score: i32) -> String {
+    if score > MATE_THRESHOLD { format!("#{}", (MATE - score + 1) / 2) }
+    else if score < -MATE_THRESHOLD { format!("#-{}", (MATE + score + 1) / 2) }
+    else { format!("{:.2}", score as f64 / 100.0) }
+}
+
+// Write a game as PGN: the tags, then the movetext wrapped to LINE_LENGTH columns
+pub fn write_game(game: &Game) -> String {
+    let mut text = String::new();
+    for (name, value) in &game.tags {
+        text.push_str(&format!("[{} \"{}\"]\n", name, value.replace('"', "\\\"")));
+    }
+    text.push('\n');
+
+    let mut words: Vec<String> = Vec::new();
+    write_line(&mut words, &game.start, &game.moves);
+    words.push(game.result().to_string());
+
+    let mut line = String::new();
+    for word in words {
+        if !line.is_empty() && line.len() + 1 + word.len() > LINE_LENGTH {
+            text.push_str(&line);
+            text.push('\n');
+            line.clear();
+        }
+        if !line.is_empty() { line.push(' '); }
+        line.push_str(&word);
+    }
+    text.push_str(&line);
+    text.push_str("\n\n");
+    text
+}
+
+fn write_line(words: &mut Vec<String>, start: &Chess, moves: &[PgnMove]) {
+    let mut board = start.clone();
+    // Black's move needs its number after anything that interrupts the movetext
+    let mut needs_number = true;
+
+    for pgn_move in moves {
+        if let Some(comment) = &pgn_move.comment_before {
+            push_comment(words, comment);
+            needs_number = true;
+        }
+        // The move number is kept in the same word as the move so a line never breaks between them
+        let number = board.fullmoves().get();
+        let prefix = if board.turn() == Color::White { format!("{}. ", number) }
+        else if needs_number { format!("{}... ", number) }
+        else { String::new() };
+
+        let before = board.clone();
+        let san = SanPlus::from_move_and_play_unchecked(&mut board, &pgn_move.mv);
+        words.push(format!("{}{}", prefix, san));
+        needs_number = false;
+
+        for nag in &pgn_move.nags { words.push(format!("${}", nag)); }
+        let eval = pgn_move.eval.map(|score| format!("[%eval {}]", format_eval(score)));
+        let comment: Vec<&str> = eval.as_deref().into_iter().chain(pgn_move.comment.as_deref()).collect();
+        if !comment.is_empty() {
+            push_comment(words, &comment.join(" "));
+            needs_number = true;
+        }
+
+        for variation in &pgn_move.variations {
+            let first = words.len();
+            write_line(words, &before, variation);
+            if words.len() > first {
+                words[first].insert(0, '(');
+                if let Some(last) = words.last_mut() { last.push(')'); }
+            }
+            needs_number = true;
+        }
+    }
+}
+
+// Comments are split into words so long ones wrap like the moves
+fn push_comment(words: &mut Vec<String>, comment: &str) {
+    let comment_words: Vec<&str> = comment.split_whitespace().collect();
+    for (index, word) in comment_words.iter().enumerate() {
+        let mut word = word.to_string();
+        if index == 0 { word.insert(0, '{'); }
+        if index == comment_words.len() - 1 { word.push('}'); }
+        words.push(word);
+    }
+}
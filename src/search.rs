@@ -1,18 +1,37 @@
-use crate::evaluate::{evaluate_position, evaluate_outcome};
+use crate::evaluate::{evaluate_position, evaluate_position_cached, evaluate_outcome};
+use crate::params::EvalParams;
+use crate::pawns::PawnTable;
+use crate::tablebase::Tablebases;
 use crate::transposition::{TranspositionTable, Bound, TTEntry};
+use crate::timeman::{TimeManager, SearchLimits, SearchControl};
+use std::sync::Arc;
+
 use shakmaty::{Chess, Move, Position, MoveList, zobrist::{Zobrist64,ZobristHash}, EnPassantMode};
-use std::time::Instant;
+
+// A root move with its score, one per MultiPV line
+pub type Line = (Move, i32);
+
+// Called after every completed iteration with the depth, the lines, the table holding their PVs and the node count
+pub type IterationInfo<'a> = dyn FnMut(usize, &[Line], &TranspositionTable, usize) + 'a;
+
+// How often (in nodes) the quiescence search checks the time, node and stop limits
+const QUIESCE_CHECK_NODES: usize = 256;
 
 pub fn negamax(
     board: &Chess, 
     depth: usize, 
     ply: usize, 
-    end_time: Instant,
+    limits: &SearchLimits,
     pvs: bool,
     mut alpha: i32,
     beta: i32,
     t_table: &mut TranspositionTable,
-    evaluate_count: &mut usize) -> (Option<Move>, i32) {
+    eval_params: &EvalParams,
+    pawn_table: &mut PawnTable,
+    tablebase: Option<&Tablebases>,
+    evaluate_count: &mut usize,
+    root_moves: &[Move],
+    excluded_moves: &[Move]) -> (Option<Move>, i32) {
 
     // Check if the game is over (checkmate or stalemate)
     if let Some(outcome) = board.outcome() {
@@ -20,18 +39,26 @@ pub fn negamax(
         return (None, evaluate_outcome(outcome, board.turn(), &ply));
     } 
 
+    // Positions in the tablebases have an exact result, except at the root where a move still has to be chosen
+    if ply > 0 {
+        if let Some(score) = tablebase.and_then(|tablebase| tablebase.probe_wdl(board, ply)) { return (None, score); }
+    }
+
     // Check if we are at terminal depth
     if depth == 0 { 
         //*evaluate_count += 1;
         //return (None, evaluate_position(board));
-        return (None, quiesce(board, 0, alpha, beta, evaluate_count));
+        return (None, quiesce(board, 0, limits, alpha, beta, eval_params, pawn_table, evaluate_count));
     }
 
     // Generate a hash for the board
     let hash = board.zobrist_hash::<Zobrist64>(EnPassantMode::Legal);
 
-    // Lookup this hash in the transposition table
-    if let Some(entry) = t_table.lookup(hash) {
+    // Lookup this hash in the transposition table (unless MultiPV lines are excluded, which makes the entry incomparable).
+    // At a tablebase root the entry is only reused if its move is one of the moves left to search.
+    let use_tt = excluded_moves.is_empty();
+    let root_entry = |entry: &TTEntry| root_moves.is_empty() || entry.best_move.as_ref().is_some_and(|mv| root_moves.contains(mv));
+    if let Some(entry) = t_table.lookup(hash).filter(|entry| use_tt && root_entry(entry)) {
         if entry.depth >= depth  { // If this position has been adequately explored before 
             match entry.bound {
                 Bound::Exact => return (entry.best_move.clone(), entry.value), // Exact evaluation, reuse
@@ -51,20 +78,24 @@ pub fn negamax(
 
     // Evaluate every legal move at one ply deeper
     for mve in legal_moves{ 
+        // Skip root moves that lose part of the tablebase result, or that were already reported as a better MultiPV line
+        if !root_moves.is_empty() && !root_moves.contains(&mve) { continue; }
+        if excluded_moves.contains(&mve) { continue; }
+
         let mut board_copy: Chess = board.clone(); // Copy the current state of the board
         board_copy.play_unchecked(&mve); // Play the move under consideration
         let (_, value): (Option<Move>, i32); // Initialize values to hold the evaluation results
-        (_, value) = negamax(&board_copy, depth-1, ply+1, end_time, true, -beta, -alpha, t_table, evaluate_count);
+        (_, value) = negamax(&board_copy, depth-1, ply+1, limits, true, -beta, -alpha, t_table, eval_params, pawn_table, tablebase, evaluate_count, &[], &[]);
 
         /* WIP Negascout implementation - currently this causes Elo loss
         if (i == 0) && (pvs) {
-            (_, value) = negamax(&board_copy, depth-1, ply+1, end_time, true, -beta, -alpha, t_table, evaluate_count);
+            (_, value) = negamax(&board_copy, depth-1, ply+1, limits, true, -beta, -alpha, t_table, eval_params, pawn_table, tablebase, evaluate_count, &[], &[]);
         } else {
             // Scout search with a null window
-            (_, value) = negamax(&board_copy, depth-1, ply+1, end_time, false, -alpha-1, -alpha, t_table, evaluate_count);
+            (_, value) = negamax(&board_copy, depth-1, ply+1, limits, false, -alpha-1, -alpha, t_table, eval_params, pawn_table, tablebase, evaluate_count, &[], &[]);
             if (alpha < -value) && (-value < beta) {
                 // If the search went outside the window then re-do it with a full window
-                (_, value) = negamax(&board_copy, depth-1, ply+1, end_time, false, -beta, -alpha, t_table, evaluate_count);
+                (_, value) = negamax(&board_copy, depth-1, ply+1, limits, false, -beta, -alpha, t_table, eval_params, pawn_table, tablebase, evaluate_count, &[], &[]);
             }
         }
         */
@@ -77,8 +108,8 @@ pub fn negamax(
         alpha = alpha.max(-value); // Update alpha
         if alpha >= beta { break } // Alpha-beta pruning
 
-        // If time is expired stop searching and return what you have
-        if Instant::now() >= end_time {return (best_move, best_value);}
+        // If time or the node budget is expired stop searching and return what you have
+        if limits.expired(*evaluate_count) {return (best_move, best_value);}
     }
 
     // Determine the bound type to store in the TT
@@ -93,7 +124,7 @@ pub fn negamax(
         depth: depth,
         bound: bound,
     };
-    t_table.store(hash, entry);
+    if use_tt { t_table.store(hash, entry); }
 
     return (best_move, best_value);
 
@@ -102,8 +133,11 @@ pub fn negamax(
 fn quiesce(
     board: &Chess,
     ply: usize, 
+    limits: &SearchLimits,
     mut alpha: i32,
     beta: i32,
+    eval_params: &EvalParams,
+    pawn_table: &mut PawnTable,
     evaluate_count: &mut usize) -> i32 {
 
     // Check if the game is over (checkmate or stalemate)
@@ -114,10 +148,11 @@ fn quiesce(
 
     // Take the static score of this node
     *evaluate_count += 1;
-    let stand_pat_score = evaluate_position(&board);
+    let stand_pat_score = evaluate_position_cached(&board, eval_params, pawn_table);
 
-    // If at terminal ply return the current static evaluation
-    if ply >= 3 {
+    // If at terminal ply, or out of time or nodes, return the current static evaluation.
+    // The limits are only checked every few nodes, reading the clock at every node is too slow.
+    if ply >= 3 || (evaluate_count.is_multiple_of(QUIESCE_CHECK_NODES) && limits.expired(*evaluate_count)) {
         return stand_pat_score;
     }
 
@@ -135,7 +170,7 @@ fn quiesce(
         else {
             let mut board_copy: Chess = board.clone(); // Copy the current state of the board
             board_copy.play_unchecked(&mve); // Play the move under consideration
-            value = quiesce(&board_copy, ply+1, -beta, -alpha, evaluate_count);
+            value = quiesce(&board_copy, ply+1, limits, -beta, -alpha, eval_params, pawn_table, evaluate_count);
         }
 
         alpha = alpha.max(-value); // Update alpha
@@ -148,43 +183,123 @@ fn quiesce(
     
 }
 
+// Follow the quiescence search down to the quiet position whose static evaluation decides its score
+pub fn quiet_leaf(
+    board: &Chess,
+    ply: usize,
+    mut alpha: i32,
+    beta: i32,
+    eval_params: &EvalParams) -> (i32, Chess) {
+
+    // Game over and terminal-ply positions are leaves themselves
+    if let Some(outcome) = board.outcome() {
+        return (evaluate_outcome(outcome, board.turn(), &ply), board.clone());
+    }
+    let stand_pat_score = evaluate_position(&board, eval_params);
+    if ply >= 3 || stand_pat_score >= beta {
+        return (stand_pat_score, board.clone());
+    }
+
+    let mut leaf: Chess = board.clone();
+    alpha = alpha.max(stand_pat_score);
+
+    for mve in board.legal_moves() {
+        if !mve.is_capture() && !mve.is_promotion() {continue;}
+        let mut board_copy: Chess = board.clone();
+        board_copy.play_unchecked(&mve);
+        let (value, child_leaf) = quiet_leaf(&board_copy, ply+1, -beta, -alpha, eval_params);
+
+        // The leaf of the best capture sequence replaces the standing position
+        if -value > alpha {
+            alpha = -value;
+            leaf = child_leaf;
+        }
+        if alpha >= beta {
+            break
+        }
+    }
+
+    return (alpha, leaf);
+}
+
 pub fn iterative_deepening(
     board: &Chess,
     max_depth: usize,
-    end_time: Instant,
+    time_manager: &mut TimeManager,
     t_table: &mut TranspositionTable,
-    evaluate_count: &mut usize) -> (Option<Move>, i32, usize) {
+    eval_params: &EvalParams,
+    tablebase: Option<&Tablebases>,
+    evaluate_count: &mut usize,
+    multi_pv: usize,
+    info: &mut IterationInfo) -> (Option<Move>, i32, usize) {
         
-    let mut best_move: Option<Move> = None;
-    let mut best_eval = -i32::MAX;
+    // In a tablebase position only the moves that keep the best result are searched
+    let excluded_root_moves = tablebase.map_or(Vec::new(), |tablebase| { tablebase.reset_hits(); tablebase.excluded_root_moves(board) });
+
+    // Fall back to the TT move (or the first ordered root move) so a legal move is always available
+    let root_moves: Vec<Move> = sort_moves(board, board.legal_moves(), t_table).into_iter().filter(|mv| !excluded_root_moves.contains(mv)).collect();
+    let searched_root_moves: &[Move] = if excluded_root_moves.is_empty() { &[] } else { &root_moves };
+    let mut best_move: Option<Move> = root_moves.first().cloned();
+    if best_move.is_none() {
+        // Checkmate or stalemate, there is nothing to search
+        let score = board.outcome().map_or(0, |outcome| evaluate_outcome(outcome, board.turn(), &0));
+        return (None, score, 0);
+    }
+
+    // Pawn structure scores depend on the parameters, so every search starts with an empty table
+    let mut pawn_table = PawnTable::new();
+    let mut best_eval = evaluate_position(board, eval_params);
     let mut max_depth_reached: usize = 0;
+    let limits = time_manager.limits();
 
     for depth in 1..(max_depth+1) {
-        let depth_start_time = Instant::now();
+        let depth_start_time = time_manager.elapsed(*evaluate_count);
         /* WIP Aspiration Window code 
         let (mut alpha, mut beta): (i32, i32);
         let (mut mv, mut score): (Option<Move>, i32);
         if depth <= 3{ (alpha, beta) = (-i32::MAX, i32::MAX); } // No aspiration window at shallow depth
         else {(alpha, beta) = (best_eval-30, best_eval+30); } // 50 centipawn aspiration window
         loop {
-            (mv, score) = negamax(board, depth, 0, end_time, true, alpha, beta, t_table, evaluate_count);
+            (mv, score) = negamax(board, depth, 0, &limits, true, alpha, beta, t_table, eval_params, &mut pawn_table, tablebase, evaluate_count, searched_root_moves, &[]);
             if -score >= alpha && -score <= beta { break; }
             else if -score < alpha { alpha -= 200; println!("Failed low depth {}", depth);}
             else if -score > beta { beta += 200; println!("Failed high depth {}", depth);}
         }
         */
         
-        let (mv, score) = negamax(board, depth, 0, end_time, true, -i32::MAX, i32::MAX, t_table, evaluate_count);
+        let (mv, score) = negamax(board, depth, 0, &limits, true, -i32::MAX, i32::MAX, t_table, eval_params, &mut pawn_table, tablebase, evaluate_count, searched_root_moves, &[]);
         
-        let depth_duration = depth_start_time.elapsed();
-        if Instant::now() >= end_time {break;}
+        if limits.expired(*evaluate_count) {
+            // A partially searched first iteration is still better than the unsearched fallback
+            if max_depth_reached == 0 {
+                if let Some(mv) = mv { best_move = Some(mv); best_eval = score; }
+            }
+            break;
+        }
 
         if let Some(mv) = mv {
+            // Search the remaining MultiPV lines, excluding the root moves already found
+            let mut lines: Vec<Line> = vec![(mv.clone(), score)];
+            while lines.len() < multi_pv.min(root_moves.len()) {
+                let excluded_moves: Vec<Move> = lines.iter().map(|(line_move, _)| line_move.clone()).collect();
+                let (line_move, line_score) = negamax(board, depth, 0, &limits, true, -i32::MAX, i32::MAX, t_table, eval_params, &mut pawn_table, tablebase, evaluate_count, searched_root_moves, &excluded_moves);
+                if limits.expired(*evaluate_count) { break; }
+                match line_move {
+                    Some(line_move) => lines.push((line_move, line_score)),
+                    None => break,
+                }
+            }
+            let depth_duration = time_manager.elapsed(*evaluate_count).saturating_sub(depth_start_time);
+            info(depth, &lines, t_table, *evaluate_count);
+
+            // Let the time manager react to an unstable best move or a falling score
+            if max_depth_reached > 0 { time_manager.update(best_move.as_ref() != Some(&mv), best_eval, score); }
+
             best_eval = score;
             best_move = Some(mv);
             max_depth_reached = depth;
         
-        if (Instant::now() + 4*depth_duration) >= end_time {
+        if time_manager.should_stop(*evaluate_count, depth_duration) {
             return (best_move, best_eval, max_depth_reached);
             }
         }
@@ -193,6 +308,39 @@ pub fn iterative_deepening(
     (best_move, best_eval, max_depth_reached)
 }
 
+// Search a position outside of a game, to a fixed depth or for a fixed number of milliseconds
+pub fn fixed_search(
+    board: &Chess,
+    depth: usize,
+    move_time: Option<u64>,
+    t_table: &mut TranspositionTable,
+    eval_params: &EvalParams,
+    tablebase: Option<&Tablebases>) -> (Option<Move>, i32, usize) {
+
+    let mut time_manager = TimeManager::new(None, 0, None, 0, Arc::new(SearchControl::new(false)));
+    time_manager.set_move_time(move_time);
+    let mut evaluate_count = 0;
+    // Tools searching many positions have no use for UCI info lines
+    iterative_deepening(board, depth, &mut time_manager, t_table, eval_params, tablebase, &mut evaluate_count, 1, &mut |_, _, _, _| {})
+}
+
+// Follow the best moves stored in the transposition table to recover the principal variation
+pub fn extract_pv(board: &Chess, first_move: &Move, t_table: &TranspositionTable, max_length: usize) -> Vec<Move> {
+    let mut pv: Vec<Move> = vec![first_move.clone()];
+    let mut position: Chess = board.clone();
+    position.play_unchecked(first_move);
+
+    while pv.len() < max_length.max(1) {
+        let hash = position.zobrist_hash::<Zobrist64>(EnPassantMode::Legal);
+        let Some(mv) = t_table.lookup(hash).and_then(|entry| entry.best_move.clone()) else { break; };
+        // Guard against hash collisions putting an illegal move in the line
+        if !position.legal_moves().contains(&mv) { break; }
+        position.play_unchecked(&mv);
+        pv.push(mv);
+    }
+    pv
+}
+
 // Sort moves based on priority.
 fn sort_moves(
     board: &Chess,
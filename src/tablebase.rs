@@ -0,0 +1,97 @@
+use shakmaty::{Chess, Move, Position};
+use shakmaty_syzygy::{Tablebase, Wdl};
+use std::env;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+
+// Score of a tablebase win, below the mate scores but above every evaluation (including the known wins of the endgame module)
+pub const TB_WIN: i32 = 8000;
+
+// Syzygy tables loaded from SyzygyPath, shared with the search thread
+pub struct Tablebases {
+    tables: Tablebase<Chess>,
+    probe_limit: AtomicUsize, // Positions with more pieces than this are searched normally
+    hits: AtomicU64,          // Successful probes since the last reset, reported as tbhits
+}
+
+impl Tablebases {
+    // Load the tables of every directory in a SyzygyPath, separated like the PATH variable
+    pub fn load(path: &str, probe_limit: usize) -> Result<Tablebases, String> {
+        let mut tables = Tablebase::new();
+        let mut count = 0;
+        for directory in env::split_paths(path) {
+            count += tables.add_directory(&directory).map_err(|error| format!("could not read {}: {}", directory.display(), error))?;
+        }
+        if count == 0 { return Err(format!("no tables found in {}", path)); }
+
+        Ok(Tablebases {
+            tables,
+            probe_limit: AtomicUsize::new(probe_limit),
+            hits: AtomicU64::new(0),
+        })
+    }
+
+    // Largest number of pieces covered by the loaded tables
+    pub fn max_pieces(&self) -> usize {
+        self.tables.max_pieces()
+    }
+
+    pub fn set_probe_limit(&self, probe_limit: usize) {
+        self.probe_limit.store(probe_limit, Ordering::Relaxed);
+    }
+
+    pub fn hits(&self) -> u64 {
+        self.hits.load(Ordering::Relaxed)
+    }
+
+    pub fn reset_hits(&self) {
+        self.hits.store(0, Ordering::Relaxed);
+    }
+
+    // Syzygy tables don't include castling rights, and only cover positions with few pieces
+    fn can_probe(&self, board: &Chess) -> bool {
+        let max_pieces = self.probe_limit.load(Ordering::Relaxed).min(self.tables.max_pieces());
+        board.board().occupied().count() <= max_pieces && board.castles().is_empty()
+    }
+
+    // Exact result of a position from the side to move's point of view. WDL tables ignore the fifty move counter,
+    // so they are only trusted right after a capture or pawn move. Cursed wins and blessed losses are draws.
+    pub fn probe_wdl(&self, board: &Chess, ply: usize) -> Option<i32> {
+        if board.halfmoves() != 0 || !self.can_probe(board) { return None; }
+        let wdl = self.tables.probe_wdl_after_zeroing(board).ok()?;
+        self.hits.fetch_add(1, Ordering::Relaxed);
+        match wdl {
+            Wdl::Win => Some(TB_WIN - ply as i32),
+            Wdl::Loss => Some(-TB_WIN + ply as i32),
+            _ => Some(0),
+        }
+    }
+
+    // Root moves that give away part of the tablebase result. Only the moves that win fastest (or lose slowest)
+    // are left to the search, which otherwise can't tell won positions apart and may never make progress.
+    pub fn excluded_root_moves(&self, board: &Chess) -> Vec<Move> {
+        if !self.can_probe(board) { return Vec::new(); }
+
+        let mut ranked: Vec<(Move, (u8, i32))> = Vec::new();
+        for mv in board.legal_moves() {
+            let mut child = board.clone();
+            child.play_unchecked(&mv);
+            // DTZ of the position after the move is from the opponent's point of view, negative means we win
+            let dtz = if child.is_checkmate() { 0 } else {
+                match self.tables.probe_dtz(&child) {
+                    Ok(dtz) => dtz.ignore_rounding().0,
+                    Err(_) => return Vec::new(), // Missing table, leave every move to the search
+                }
+            };
+            // A result the fifty move rule reaches first, counting the moves already played since the last zeroing move, is a draw
+            let drawn = !child.is_checkmate() && child.halfmoves() as i32 + dtz.abs() > 100;
+            let rank = if child.is_checkmate() || (dtz < 0 && !drawn) { (0, -dtz) } // Wins, the fastest first
+            else if dtz == 0 || drawn { (1, 0) }                                    // Draws
+            else { (2, -dtz) };                                                     // Losses, the slowest first
+            ranked.push((mv, rank));
+        }
+        self.hits.fetch_add(ranked.len() as u64, Ordering::Relaxed);
+
+        let Some(best) = ranked.iter().map(|(_, rank)| *rank).min() else { return Vec::new(); };
+        ranked.into_iter().filter(|(_, rank)| *rank != best).map(|(mv, _)| mv).collect()
+    }
+}
@@ -0,0 +1,301 @@
+// Material values and piece-square tables for each evaluation profile.
+// Tables are laid out as seen from White's side of the board, a8 first and h1 last.
+
+// Material values indexed by role (pawn, knight, bishop, rook, queen, king)
+pub const SIMPLIFIED_VALUES: [i32; 6] = [100, 300, 300, 500, 900, 0];
+pub const SIMPLIFIED_VALUES_ENDGAME: [i32; 6] = [120, 280, 310, 530, 940, 0];
+
+pub const SIMPLIFIED_TABLES: [[i32; 64]; 6] = [PAWN_TABLE, KNIGHT_TABLE, BISHOP_TABLE, ROOK_TABLE, QUEEN_TABLE, KING_TABLE];
+pub const SIMPLIFIED_TABLES_ENDGAME: [[i32; 64]; 6] = [
+    PAWN_TABLE_ENDGAME, KNIGHT_TABLE_ENDGAME, BISHOP_TABLE_ENDGAME, ROOK_TABLE_ENDGAME, QUEEN_TABLE_ENDGAME, KING_TABLE_ENDGAME];
+
+// PeSTO tables and values (Ronald Friederich's tapered evaluation, as published on the chess programming wiki)
+pub const PESTO_VALUES: [i32; 6] = [82, 337, 365, 477, 1025, 0];
+pub const PESTO_VALUES_ENDGAME: [i32; 6] = [94, 281, 297, 512, 936, 0];
+
+pub const PESTO_TABLES: [[i32; 64]; 6] = [
+    PESTO_PAWN_TABLE, PESTO_KNIGHT_TABLE, PESTO_BISHOP_TABLE, PESTO_ROOK_TABLE, PESTO_QUEEN_TABLE, PESTO_KING_TABLE];
+pub const PESTO_TABLES_ENDGAME: [[i32; 64]; 6] = [
+    PESTO_PAWN_TABLE_ENDGAME, PESTO_KNIGHT_TABLE_ENDGAME, PESTO_BISHOP_TABLE_ENDGAME, PESTO_ROOK_TABLE_ENDGAME, PESTO_QUEEN_TABLE_ENDGAME, PESTO_KING_TABLE_ENDGAME];
+
+const PAWN_TABLE: [i32; 64] = [
+    0,  0,  0,  0,  0,  0,  0,  0,
+    50, 50, 50, 50, 50, 50, 50, 50,
+    10, 10, 20, 30, 30, 20, 10, 10,
+     5,  5, 10, 25, 25, 10,  5,  5,
+     0,  0,  0, 20, 20,  0,  0,  0,
+     5, -5,-10,  0,  0,-10, -5,  5,
+     5, 10, 10,-20,-20, 10, 10,  5,
+     0,  0,  0,  0,  0,  0,  0,  0
+];
+
+const KNIGHT_TABLE: [i32; 64] = [
+    -50,-40,-30,-30,-30,-30,-40,-50,
+-40,-20,  0,  0,  0,  0,-20,-40,
+-30,  0, 10, 15, 15, 10,  0,-30,
+-30,  5, 15, 20, 20, 15,  5,-30,
+-30,  0, 15, 20, 20, 15,  0,-30,
+-30,  5, 10, 15, 15, 10,  5,-30,
+-40,-20,  0,  5,  5,  0,-20,-40,
+-50,-40,-30,-30,-30,-30,-40,-50
+];
+
+const BISHOP_TABLE: [i32; 64] = [
+    -20,-10,-10,-10,-10,-10,-10,-20,
+-10,  0,  0,  0,  0,  0,  0,-10,
+-10,  0,  5, 10, 10,  5,  0,-10,
+-10,  5,  5, 10, 10,  5,  5,-10,
+-10,  0, 10, 10, 10, 10,  0,-10,
+-10, 10, 10, 10, 10, 10, 10,-10,
+-10,  5,  0,  0,  0,  0,  5,-10,
+-20,-10,-10,-10,-10,-10,-10,-20
+];
+
+const ROOK_TABLE: [i32; 64] = [
+    0,  0,  0,  0,  0,  0,  0,  0,
+    5, 10, 10, 10, 10, 10, 10,  5,
+   -5,  0,  0,  0,  0,  0,  0, -5,
+   -5,  0,  0,  0,  0,  0,  0, -5,
+   -5,  0,  0,  0,  0,  0,  0, -5,
+   -5,  0,  0,  0,  0,  0,  0, -5,
+   -5,  0,  0,  0,  0,  0,  0, -5,
+    0,  0,  0,  5,  5,  0,  0,  0
+];
+
+const QUEEN_TABLE: [i32;64] = [
+    -20,-10,-10, -5, -5,-10,-10,-20,
+-10,  0,  0,  0,  0,  0,  0,-10,
+-10,  0,  5,  5,  5,  5,  0,-10,
+ -5,  0,  5,  5,  5,  5,  0, -5,
+  0,  0,  5,  5,  5,  5,  0, -5,
+-10,  5,  5,  5,  5,  5,  0,-10,
+-10,  0,  5,  0,  0,  0,  0,-10,
+-20,-10,-10, -5, -5,-10,-10,-20
+];
+
+const KING_TABLE: [i32; 64] = [
+    -30,-40,-40,-50,-50,-40,-40,-30,
+-30,-40,-40,-50,-50,-40,-40,-30,
+-30,-40,-40,-50,-50,-40,-40,-30,
+-30,-40,-40,-50,-50,-40,-40,-30,
+-20,-30,-30,-40,-40,-30,-30,-20,
+-10,-20,-20,-20,-20,-20,-20,-10,
+ 20, 20,  0,  0,  0,  0, 20, 20,
+ 20, 30, 10,  0,  0, 10, 30, 20
+];
+
+const PAWN_TABLE_ENDGAME: [i32; 64] = [
+     0,  0,  0,  0,  0,  0,  0,  0,
+    80, 80, 80, 80, 80, 80, 80, 80,
+    50, 50, 50, 50, 50, 50, 50, 50,
+    30, 30, 30, 30, 30, 30, 30, 30,
+    15, 15, 15, 15, 15, 15, 15, 15,
+     5,  5,  5,  5,  5,  5,  5,  5,
+     0,  0,  0,  0,  0,  0,  0,  0,
+     0,  0,  0,  0,  0,  0,  0,  0
+];
+
+const KNIGHT_TABLE_ENDGAME: [i32; 64] = [
+    -40,-30,-20,-20,-20,-20,-30,-40,
+-30,-10,  0,  5,  5,  0,-10,-30,
+-20,  0, 10, 15, 15, 10,  0,-20,
+-20,  5, 15, 20, 20, 15,  5,-20,
+-20,  5, 15, 20, 20, 15,  5,-20,
+-20,  0, 10, 15, 15, 10,  0,-20,
+-30,-10,  0,  5,  5,  0,-10,-30,
+-40,-30,-20,-20,-20,-20,-30,-40
+];
+
+const BISHOP_TABLE_ENDGAME: [i32; 64] = [
+    -15,-10,-10,-10,-10,-10,-10,-15,
+-10,  0,  0,  0,  0,  0,  0,-10,
+-10,  0,  5,  5,  5,  5,  0,-10,
+-10,  0,  5, 10, 10,  5,  0,-10,
+-10,  0,  5, 10, 10,  5,  0,-10,
+-10,  0,  5,  5,  5,  5,  0,-10,
+-10,  0,  0,  0,  0,  0,  0,-10,
+-15,-10,-10,-10,-10,-10,-10,-15
+];
+
+const ROOK_TABLE_ENDGAME: [i32; 64] = [
+     5,  5,  5,  5,  5,  5,  5,  5,
+    10, 10, 10, 10, 10, 10, 10, 10,
+     0,  0,  0,  0,  0,  0,  0,  0,
+     0,  0,  0,  0,  0,  0,  0,  0,
+     0,  0,  0,  0,  0,  0,  0,  0,
+     0,  0,  0,  0,  0,  0,  0,  0,
+     0,  0,  0,  0,  0,  0,  0,  0,
+     0,  0,  0,  0,  0,  0,  0,  0
+];
+
+const QUEEN_TABLE_ENDGAME: [i32; 64] = [
+    -20,-10,-10, -5, -5,-10,-10,-20,
+-10,  0,  5,  5,  5,  5,  0,-10,
+-10,  5, 10, 10, 10, 10,  5,-10,
+ -5,  5, 10, 15, 15, 10,  5, -5,
+ -5,  5, 10, 15, 15, 10,  5, -5,
+-10,  5, 10, 10, 10, 10,  5,-10,
+-10,  0,  5,  5,  5,  5,  0,-10,
+-20,-10,-10, -5, -5,-10,-10,-20
+];
+
+const KING_TABLE_ENDGAME: [i32; 64] = [
+    -50,-40,-30,-20,-20,-30,-40,-50,
+-30,-20,-10,  0,  0,-10,-20,-30,
+-30,-10, 20, 30, 30, 20,-10,-30,
+-30,-10, 30, 40, 40, 30,-10,-30,
+-30,-10, 30, 40, 40, 30,-10,-30,
+-30,-10, 20, 30, 30, 20,-10,-30,
+-30,-30,  0,  0,  0,  0,-30,-30,
+-50,-30,-30,-30,-30,-30,-30,-50
+];
+
+const PESTO_PAWN_TABLE: [i32; 64] = [
+    0,   0,   0,   0,   0,   0,  0,   0,
+     98, 134,  61,  95,  68, 126, 34, -11,
+     -6,   7,  26,  31,  65,  56, 25, -20,
+    -14,  13,   6,  21,  23,  12, 17, -23,
+    -27,  -2,  -5,  12,  17,   6, 10, -25,
+    -26,  -4,  -4, -10,   3,   3, 33, -12,
+    -35,  -1, -20, -23, -15,  24, 38, -22,
+      0,   0,   0,   0,   0,   0,  0,   0,];
+
+const PESTO_KNIGHT_TABLE: [i32; 64] = [
+    -167, -89, -34, -49,  61, -97, -15, -107,
+     -73, -41,  72,  36,  23,  62,   7,  -17,
+     -47,  60,  37,  65,  84, 129,  73,   44,
+      -9,  17,  19,  53,  37,  69,  18,   22,
+     -13,   4,  16,  13,  28,  19,  21,   -8,
+     -23,  -9,  12,  10,  19,  17,  25,  -16,
+     -29, -53, -12,  -3,  -1,  18, -14,  -19,
+    -105, -21, -58, -33, -17, -28, -19,  -23,];
+
+const PESTO_BISHOP_TABLE: [i32; 64] = [
+    -29,   4, -82, -37, -25, -42,   7,  -8,
+    -26,  16, -18, -13,  30,  59,  18, -47,
+    -16,  37,  43,  40,  35,  50,  37,  -2,
+     -4,   5,  19,  50,  37,  37,   7,  -2,
+     -6,  13,  13,  26,  34,  12,  10,   4,
+      0,  15,  15,  15,  14,  27,  18,  10,
+      4,  15,  16,   0,   7,  21,  33,   1,
+    -33,  -3, -14, -21, -13, -12, -39, -21,];
+
+const PESTO_ROOK_TABLE: [i32; 64] = [
+    32,  42,  32,  51, 63,  9,  31,  43,
+     27,  32,  58,  62, 80, 67,  26,  44,
+     -5,  19,  26,  36, 17, 45,  61,  16,
+    -24, -11,   7,  26, 24, 35,  -8, -20,
+    -36, -26, -12,  -1,  9, -7,   6, -23,
+    -45, -25, -16, -17,  3,  0,  -5, -33,
+    -44, -16, -20,  -9, -1, 11,  -6, -71,
+    -19, -13,   1,  17, 16,  7, -37, -26,];
+
+const PESTO_QUEEN_TABLE: [i32; 64] = [
+    -28,   0,  29,  12,  59,  44,  43,  45,
+    -24, -39,  -5,   1, -16,  57,  28,  54,
+    -13, -17,   7,   8,  29,  56,  47,  57,
+    -27, -27, -16, -16,  -1,  17,  -2,   1,
+     -9, -26,  -9, -10,  -2,  -4,   3,  -3,
+    -14,   2, -11,  -2,  -5,   2,  14,   5,
+    -35,  -8,  11,   2,   8,  15,  -3,   1,
+     -1, -18,  -9,  10, -15, -25, -31, -50,];
+
+const PESTO_KING_TABLE: [i32; 64] = [
+    -65,  23,  16, -15, -56, -34,   2,  13,
+    29,  -1, -20,  -7,  -8,  -4, -38, -29,
+    -9,  24,   2, -16, -20,   6,  22, -22,
+   -17, -20, -12, -27, -30, -25, -14, -36,
+   -49,  -1, -27, -39, -46, -44, -33, -51,
+   -14, -14, -22, -46, -44, -30, -15, -27,
+     1,   7,  -8, -64, -43, -16,   9,   8,
+   -15,  36,  12, -54,   8, -28,  24,  14,];
+
+const PESTO_PAWN_TABLE_ENDGAME: [i32; 64] = [
+      0,   0,   0,   0,   0,   0,   0,   0,
+    178, 173, 158, 134, 147, 132, 165, 187,
+     94, 100,  85,  67,  56,  53,  82,  84,
+     32,  24,  13,   5,  -2,   4,  17,  17,
+     13,   9,  -3,  -7,  -7,  -8,   3,  -1,
+      4,   7,  -6,   1,   0,  -5,  -1,  -8,
+     13,   8,   8,  10,  13,   0,   2,  -7,
+      0,   0,   0,   0,   0,   0,   0,   0,];
+
+const PESTO_KNIGHT_TABLE_ENDGAME: [i32; 64] = [
+    -58, -38, -13, -28, -31, -27, -63, -99,
+    -25,  -8, -25,  -2,  -9, -25, -24, -52,
+    -24, -20,  10,   9,  -1,  -9, -19, -41,
+    -17,   3,  22,  22,  22,  11,   8, -18,
+    -18,  -6,  16,  25,  16,  17,   4, -18,
+    -23,  -3,  -1,  15,  10,  -3, -20, -22,
+    -42, -20, -10,  -5,  -2, -20, -23, -44,
+    -29, -51, -23, -15, -22, -18, -50, -64,];
+
+const PESTO_BISHOP_TABLE_ENDGAME: [i32; 64] = [
+    -14, -21, -11,  -8,  -7,  -9, -17, -24,
+     -8,  -4,   7, -12,  -3, -13,  -4, -14,
+      2,  -8,   0,  -1,  -2,   6,   0,   4,
+     -3,   9,  12,   9,  14,  10,   3,   2,
+     -6,   3,  13,  19,   7,  10,  -3,  -9,
+    -12,  -3,   8,  10,  13,   3,  -7, -15,
+    -14, -18,  -7,  -1,   4,  -9, -15, -27,
+    -23,  -9, -23,  -5,  -9, -16,  -5, -17,];
+
+const PESTO_ROOK_TABLE_ENDGAME: [i32; 64] = [
+     13,  10,  18,  15,  12,  12,   8,   5,
+     11,  13,  13,  11,  -3,   3,   8,   3,
+      7,   7,   7,   5,   4,  -3,  -5,  -3,
+      4,   3,  13,   1,   2,   1,  -1,   2,
+      3,   5,   8,   4,  -5,  -6,  -8, -11,
+     -4,   0,  -5,  -1,  -7, -12,  -8, -16,
+     -6,  -6,   0,   2,  -9,  -9, -11,  -3,
+     -9,   2,   3,  -1,  -5, -13,   4, -20,];
+
+const PESTO_QUEEN_TABLE_ENDGAME: [i32; 64] = [
+     -9,  22,  22,  27,  27,  19,  10,  20,
+    -17,  20,  32,  41,  58,  25,  30,   0,
+    -20,   6,   9,  49,  47,  35,  19,   9,
+      3,  22,  24,  45,  57,  40,  57,  36,
+    -18,  28,  19,  47,  31,  34,  39,  23,
+    -16, -27,  15,   6,   9,  17,  10,   5,
+    -22, -23, -30, -16, -16, -23, -36, -32,
+    -33, -28, -22, -43,  -5, -32, -20, -41,];
+
+const PESTO_KING_TABLE_ENDGAME: [i32; 64] = [
+    -74, -35, -18, -18, -11,  15,   4, -17,
+    -12,  17,  14,  17,  17,  38,  23,  11,
+     10,  17,  23,  15,  20,  45,  44,  13,
+     -8,  22,  24,  27,  26,  33,  26,   3,
+    -18,  -4,  21,  24,  27,  23,   9, -11,
+    -19,  -3,  11,  21,  23,  16,   7,  -9,
+    -27, -11,   4,  13,  14,   4,  -5, -17,
+    -53, -34, -21, -11, -28, -14, -24, -43,];
+
+// Mobility bonuses indexed by the number of safe squares a piece attacks, shared by both profiles
+pub const KNIGHT_MOBILITY: [i32; 9] = [-31, -26, -6, -2, 1, 6, 11, 14, 16];
+pub const KNIGHT_MOBILITY_ENDGAME: [i32; 9] = [-40, -28, -15, -7, 4, 7, 11, 13, 16];
+
+pub const BISHOP_MOBILITY: [i32; 14] = [-24, -10, 8, 13, 19, 25, 27, 31, 31, 34, 40, 40, 45, 49];
+pub const BISHOP_MOBILITY_ENDGAME: [i32; 14] = [-29, -11, -1, 6, 12, 21, 27, 28, 32, 36, 39, 43, 44, 48];
+
+pub const ROOK_MOBILITY: [i32; 15] = [-29, -13, -7, -5, -2, -1, 4, 8, 15, 14, 16, 19, 23, 24, 29];
+pub const ROOK_MOBILITY_ENDGAME: [i32; 15] = [-38, -9, 14, 27, 34, 41, 56, 59, 66, 71, 77, 82, 83, 84, 85];
+
+pub const QUEEN_MOBILITY: [i32; 28] = [
+    -19, -10,   1,   1,   7,  11,  14,  20,  21,  24,  28,  30,  30,  33,
+     33,  35,  35,  36,  39,  44,  44,  49,  51,  51,  53,  54,  56,  58,];
+pub const QUEEN_MOBILITY_ENDGAME: [i32; 28] = [
+    -18,  -7,   4,   9,  17,  27,  30,  36,  39,  46,  47,  52,  56,  60,
+     61,  63,  66,  68,  70,  71,  74,  83,  85,  87,  92,  95, 103, 106,];
+
+// King danger in centipawns indexed by attack units on the king zone, grows slowly then steeply
+pub const KING_SAFETY_TABLE: [i32; 100] = [
+      0,   0,   1,   2,   3,   5,   7,   9,  12,  15,
+     18,  22,  26,  30,  35,  39,  44,  50,  56,  62,
+     68,  75,  82,  85,  89,  97, 105, 113, 122, 131,
+    140, 150, 169, 180, 191, 202, 213, 225, 237, 248,
+    260, 272, 283, 295, 307, 319, 330, 342, 354, 366,
+    377, 389, 401, 412, 424, 436, 448, 459, 471, 483,
+    494, 500, 500, 500, 500, 500, 500, 500, 500, 500,
+    500, 500, 500, 500, 500, 500, 500, 500, 500, 500,
+    500, 500, 500, 500, 500, 500, 500, 500, 500, 500,
+    500, 500, 500, 500, 500, 500, 500, 500, 500, 500,];
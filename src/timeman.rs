@@ -0,0 +1,226 @@
+use std::sync::{Arc, Condvar, Mutex, PoisonError};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+// Budget used when the GUI gives no clock at all (e.g. a bare "go")
+const DEFAULT_BUDGET_MS: u64 = 1000;
+// Assumed number of moves left in the game when no movestogo is given
+const DEFAULT_MOVES_TO_GO: u64 = 20;
+// Below this much usable clock we switch to emergency allocation
+const EMERGENCY_MS: u64 = 1000;
+// Score drop (centipawns) between iterations that triggers an extension
+const SCORE_DROP_MARGIN: i32 = 30;
+// Upper bound on how far the soft limit can be stretched
+const MAX_EXTENSION: f64 = 2.5;
+
+// Shared between the UCI loop and the search thread to stop the search or start the clock
+pub struct SearchControl {
+    created: Instant,
+    clock_start: AtomicU64, // Milliseconds after creation at which the clock started running
+    pondering: AtomicBool,  // While pondering the clock is paused and only "stop" ends the search
+    ponder_nodes: AtomicU64, // Nodes searched while pondering, which don't count against the node budget
+    stop: AtomicBool,
+    wakeup: (Mutex<()>, Condvar), // Signalled on "stop" and "ponderhit" for a search waiting to send its move
+}
+
+impl SearchControl {
+    pub fn new(pondering: bool) -> Self {
+        Self {
+            created: Instant::now(),
+            clock_start: AtomicU64::new(0),
+            pondering: AtomicBool::new(pondering),
+            ponder_nodes: AtomicU64::new(0),
+            stop: AtomicBool::new(false),
+            wakeup: (Mutex::new(()), Condvar::new()),
+        }
+    }
+
+    pub fn stop(&self) {
+        self.stop.store(true, Ordering::Relaxed);
+        self.notify();
+    }
+
+    pub fn is_stopped(&self) -> bool {
+        self.stop.load(Ordering::Relaxed)
+    }
+
+    pub fn is_pondering(&self) -> bool {
+        self.pondering.load(Ordering::Relaxed)
+    }
+
+    // The opponent played the expected move - our clock starts running from now
+    pub fn ponder_hit(&self) {
+        self.clock_start.store(self.created.elapsed().as_millis() as u64, Ordering::Relaxed);
+        self.pondering.store(false, Ordering::Relaxed);
+        self.notify();
+    }
+
+    // Taking the lock orders the wakeup after the waiting search has checked the flags, so it can't be missed
+    fn notify(&self) {
+        let _guard = self.wakeup.0.lock().unwrap_or_else(PoisonError::into_inner);
+        self.wakeup.1.notify_all();
+    }
+
+    // Block until the best move may be sent, which is after "ponderhit" or "stop", or only "stop" for an infinite search
+    pub fn wait_for_bestmove(&self, infinite: bool) {
+        let mut guard = self.wakeup.0.lock().unwrap_or_else(PoisonError::into_inner);
+        while (self.is_pondering() || infinite) && !self.is_stopped() {
+            guard = self.wakeup.1.wait(guard).unwrap_or_else(PoisonError::into_inner);
+        }
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.created.elapsed().saturating_sub(Duration::from_millis(self.clock_start.load(Ordering::Relaxed)))
+    }
+
+    // Nodes searched since the clock started. The search reports its node count while pondering,
+    // so the nodes searched before "ponderhit" are left out of the count.
+    fn clock_nodes(&self, nodes: usize) -> usize {
+        if self.is_pondering() {
+            self.ponder_nodes.store(nodes as u64, Ordering::Relaxed);
+            return 0;
+        }
+        nodes.saturating_sub(self.ponder_nodes.load(Ordering::Relaxed) as usize)
+    }
+}
+
+// How elapsed search time is measured
+#[derive(Copy, Clone)]
+enum Clock {
+    Wall,        // Real time since the clock started, as tracked by the search control
+    Nodes(u64),  // Deterministic - nodes searched divided by a fixed nodes/ms rate
+}
+
+// The hard stopping conditions checked inside the search itself
+#[derive(Clone)]
+pub struct SearchLimits {
+    control: Arc<SearchControl>,
+    hard_limit: Option<Duration>,
+    max_nodes: Option<usize>,
+}
+
+impl SearchLimits {
+    pub fn expired(&self, nodes: usize) -> bool {
+        if self.control.is_stopped() { return true; }
+        let nodes = self.control.clock_nodes(nodes);
+        if self.control.is_pondering() { return false; }
+        if let Some(max_nodes) = self.max_nodes { if nodes >= max_nodes { return true; } }
+        if let Some(hard_limit) = self.hard_limit { if self.control.elapsed() >= hard_limit { return true; } }
+        false
+    }
+}
+
+pub struct TimeManager {
+    control: Arc<SearchControl>,
+    clock: Clock,
+    soft_limit: Option<Duration>, // Don't start a new iteration after this
+    hard_limit: Option<Duration>, // Abort the search in progress after this
+    node_limit: Option<usize>,    // Explicit node budget from "go nodes"
+    extension: f64,               // Multiplier applied to the soft limit
+}
+
+impl TimeManager {
+    pub fn new(time: Option<u64>, inc: u64, movestogo: Option<u64>, move_overhead: u64, control: Arc<SearchControl>) -> Self {
+        let (soft, hard) = match time {
+            Some(time) => Self::allocate(time, inc, movestogo, move_overhead),
+            // No clock given, fall back to a fixed budget per move
+            None => (DEFAULT_BUDGET_MS, DEFAULT_BUDGET_MS),
+        };
+
+        Self {
+            control,
+            clock: Clock::Wall,
+            soft_limit: Some(Duration::from_millis(soft)),
+            hard_limit: Some(Duration::from_millis(hard)),
+            node_limit: None,
+            extension: 1.0,
+        }
+    }
+
+    fn allocate(time: u64, inc: u64, movestogo: Option<u64>, move_overhead: u64) -> (u64, u64) {
+        // Never plan to use the time needed to send the move back to the GUI
+        let available = time.saturating_sub(move_overhead);
+        let moves_to_go = movestogo.unwrap_or(DEFAULT_MOVES_TO_GO).clamp(1, 50);
+
+        if available < EMERGENCY_MS {
+            // Emergency - play almost instantly and lean on the increment
+            let soft = available / 40 + inc / 4;
+            let hard = available / 10 + inc / 2;
+            (soft, hard.min(available / 2))
+        } else {
+            let soft = available / moves_to_go + inc * 3 / 4;
+            let hard = (soft * 4).min(available * 3 / 4);
+            (soft.min(hard), hard)
+        }
+    }
+
+    // Bound the search by a node count. Without a clock the node count is the only limit.
+    pub fn set_node_limit(&mut self, nodes: usize, has_clock: bool) {
+        self.node_limit = Some(nodes);
+        if !has_clock {
+            self.soft_limit = None;
+            self.hard_limit = None;
+        }
+    }
+
+    // Spend exactly this many milliseconds on the move, or search without a time limit (for fixed depth analysis)
+    pub fn set_move_time(&mut self, move_time: Option<u64>) {
+        self.soft_limit = move_time.map(Duration::from_millis);
+        self.hard_limit = move_time.map(Duration::from_millis);
+    }
+
+    // Measure time in nodes rather than wall clock so identical inputs give identical searches
+    pub fn set_deterministic(&mut self, nodes_per_ms: u64) {
+        self.clock = Clock::Nodes(nodes_per_ms.max(1));
+    }
+
+    // The stopping conditions for the search in progress
+    pub fn limits(&self) -> SearchLimits {
+        match self.clock {
+            Clock::Wall => SearchLimits {
+                control: self.control.clone(),
+                hard_limit: self.hard_limit,
+                max_nodes: self.node_limit,
+            },
+            Clock::Nodes(nodes_per_ms) => {
+                let hard_nodes = self.hard_limit.map(|hard| hard.as_millis() as usize * nodes_per_ms as usize);
+                let max_nodes = match (hard_nodes, self.node_limit) {
+                    (Some(a), Some(b)) => Some(a.min(b)),
+                    (a, b) => a.or(b),
+                };
+                SearchLimits { control: self.control.clone(), hard_limit: None, max_nodes }
+            }
+        }
+    }
+
+    pub fn elapsed(&self, nodes: usize) -> Duration {
+        match self.clock {
+            Clock::Wall => self.control.elapsed(),
+            Clock::Nodes(nodes_per_ms) => Duration::from_millis(self.control.clock_nodes(nodes) as u64 / nodes_per_ms),
+        }
+    }
+
+    // Called after every completed iteration to stretch the soft limit when the search is unstable
+    pub fn update(&mut self, best_move_changed: bool, previous_score: i32, score: i32) {
+        if best_move_changed { self.extension *= 1.3; }
+        if previous_score - score > SCORE_DROP_MARGIN { self.extension *= 1.2; }
+        // Decay back towards the base allocation while the search is stable
+        if !best_move_changed && previous_score - score <= SCORE_DROP_MARGIN { self.extension *= 0.9; }
+        self.extension = self.extension.clamp(1.0, MAX_EXTENSION);
+    }
+
+    // Whether iterative deepening should stop before starting another iteration
+    pub fn should_stop(&self, nodes: usize, last_iteration: Duration) -> bool {
+        if self.control.is_stopped() { return true; }
+        let elapsed = self.elapsed(nodes);
+        let nodes = self.control.clock_nodes(nodes);
+        // Keep deepening while pondering, the clock only starts on ponderhit
+        if self.control.is_pondering() { return false; }
+        if let Some(node_limit) = self.node_limit { if nodes >= node_limit { return true; } }
+        let (Some(soft_limit), Some(hard_limit)) = (self.soft_limit, self.hard_limit) else { return false; };
+        let soft_limit = soft_limit.mul_f64(self.extension).min(hard_limit);
+
+        // Stop if the soft limit has passed or the next iteration clearly can't finish in time
+        elapsed >= soft_limit || elapsed + 2 * last_iteration >= hard_limit
+    }
+}
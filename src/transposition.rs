@@ -38,4 +38,9 @@ impl TranspositionTable {
     pub fn lookup(&self, hash: HashKey) -> Option<&TTEntry> {
         self.table.get(&hash)
     }
+
+    // Forget every stored position, for a new game or a search that must not depend on earlier ones
+    pub fn clear(&mut self) {
+        self.table.clear();
+    }
 }
\ No newline at end of file
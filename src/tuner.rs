@@ -0,0 +1,114 @@
+use crate::evaluate::evaluate_position;
+use crate::params::EvalParams;
+use crate::search::quiet_leaf;
+
+use std::fs;
+
+use shakmaty::{Chess, Position, Color, CastlingMode, fen::Fen};
+
+// A quiet training position and the result of the game it came from, from White's point of view
+pub struct TuningPosition {
+    pub leaf: Chess,
+    pub result: f64,
+}
+
+// Read "<fen> <result>" lines, where the result is 1-0, 0-1, 1/2-1/2 or [1.0], [0.5], [0.0].
+// EPD records with the result in an operation, such as c9 "1-0";, are not supported.
+pub fn load_positions(path: &str, params: &EvalParams) -> Result<Vec<TuningPosition>, String> {
+    let text = fs::read_to_string(path).map_err(|error| format!("could not read {}: {}", path, error))?;
+    let mut positions = Vec::new();
+
+    for (line_number, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') { continue; }
+
+        if line.contains(';') {
+            return Err(format!("line {}: EPD operations are not supported, expected <fen> <result>", line_number + 1));
+        }
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        let (Some(result_token), fen_tokens) = (tokens.last(), &tokens[..tokens.len() - 1]) else { continue; };
+        let result = parse_result(result_token).ok_or_else(|| format!("line {}: no game result", line_number + 1))?;
+        let fen: Fen = fen_tokens.join(" ").parse().map_err(|_| format!("line {}: invalid FEN", line_number + 1))?;
+        let board: Chess = fen.into_position(CastlingMode::Standard).map_err(|_| format!("line {}: illegal position", line_number + 1))?;
+
+        // Resolve captures first so the static evaluation is only asked about quiet positions
+        let (_, leaf) = quiet_leaf(&board, 0, -i32::MAX, i32::MAX, params);
+        positions.push(TuningPosition { leaf, result });
+    }
+    Ok(positions)
+}
+
+fn parse_result(token: &str) -> Option<f64> {
+    let token = token.trim_matches(|c: char| c == '[' || c == ']' || c == '"');
+    match token {
+        "1-0" => Some(1.0),
+        "0-1" => Some(0.0),
+        "1/2-1/2" => Some(0.5),
+        _ => token.parse::<f64>().ok().filter(|result| (0.0..=1.0).contains(result)),
+    }
+}
+
+// Expected score for White given a centipawn evaluation
+fn sigmoid(score: i32, k: f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf(-k * score as f64 / 400.0))
+}
+
+pub fn mean_squared_error(positions: &[TuningPosition], params: &EvalParams, k: f64) -> f64 {
+    let total: f64 = positions.iter().map(|position| {
+        // evaluate_position scores from the side to move, the results are from White's side
+        let score = evaluate_position(&position.leaf, params);
+        let white_score = if position.leaf.turn() == Color::White { score } else { -score };
+        (position.result - sigmoid(white_score, k)).powi(2)
+    }).sum();
+    total / positions.len().max(1) as f64
+}
+
+// Find the sigmoid scaling constant that best fits the current evaluation to the results
+pub fn find_k(positions: &[TuningPosition], params: &EvalParams) -> f64 {
+    let (mut best_k, mut best_error) = (1.0, mean_squared_error(positions, params, 1.0));
+    let mut step = 0.5;
+    for _ in 0..20 {
+        for candidate in [best_k - step, best_k + step] {
+            if candidate <= 0.0 { continue; }
+            let error = mean_squared_error(positions, params, candidate);
+            if error < best_error { (best_k, best_error) = (candidate, error); }
+        }
+        step /= 2.0;
+    }
+    best_k
+}
+
+// Texel's local search: nudge each weight up or down by one and keep any change that lowers the error.
+// Stops after the given number of passes or once a full pass makes no improvement.
+pub fn local_search(
+    positions: &[TuningPosition],
+    params: &EvalParams,
+    k: f64,
+    passes: usize,
+    mut on_pass: impl FnMut(usize, f64, &EvalParams)) -> EvalParams {
+
+    let mut params = params.clone();
+    let mut weights = params.to_vector();
+    let mut best_error = mean_squared_error(positions, &params, k);
+
+    for pass in 1..(passes+1) {
+        let mut improved = false;
+        for index in 0..weights.len() {
+            for delta in [1, -1] {
+                weights[index] += delta;
+                params.set_vector(&weights);
+                let error = mean_squared_error(positions, &params, k);
+                if error < best_error {
+                    best_error = error;
+                    improved = true;
+                    break;
+                }
+                weights[index] -= delta;
+            }
+        }
+        params.set_vector(&weights);
+        on_pass(pass, best_error, &params);
+        if !improved { break; }
+    }
+    params
+}
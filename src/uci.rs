@@ -1,18 +1,162 @@
-use crate::parsers::{parse_perft_command, parse_go_command};
-use crate::search::iterative_deepening;
+use crate::parsers::{parse_perft_command, parse_go_command, parse_setoption_command};
+use crate::search::{iterative_deepening, extract_pv, Line};
 use crate::transposition::TranspositionTable;
+use crate::timeman::{TimeManager, SearchControl};
+use crate::options::EngineOptions;
+use crate::params::EvalProfile;
+use crate::evaluate::{trace_evaluation, Score};
+use crate::endgame::SCALE_NORMAL;
+use crate::tablebase::Tablebases;
+use crate::book::OpeningBook;
 
-use std::time::{Instant, Duration};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::thread::{self, JoinHandle};
+use std::time::Instant;
 use std::process;
+use std::fs;
 
-use shakmaty::{Chess, Position, uci::UciMove, CastlingMode};
+use shakmaty::{Chess, Move, Position, Color, uci::UciMove, CastlingMode};
 
 pub fn print_info() {
     println!("id name je_bot");
     println!("id author Jeffrey English");
+    println!("option name Move Overhead type spin default 30 min 0 max 5000");
+    println!("option name MultiPV type spin default 1 min 1 max 64");
+    println!("option name Ponder type check default false");
+    let profiles: Vec<String> = EvalProfile::ALL.iter().map(|profile| format!("var {}", profile.name())).collect();
+    println!("option name EvalProfile type combo default {} {}", EvalProfile::Simplified.name(), profiles.join(" "));
+    println!("option name EvalFile type string default <empty>");
+    println!("option name SyzygyPath type string default <empty>");
+    println!("option name SyzygyProbeLimit type spin default 7 min 0 max 7");
+    println!("option name OwnBook type check default false");
+    println!("option name BookFile type string default <empty>");
+    println!("option name BookDepth type spin default 16 min 0 max 100");
+    println!("option name BestBookMove type check default false");
     println!("uciok");
 }
 
+pub fn set_option(input: &str, options: &mut EngineOptions) {
+    let (name, value) = parse_setoption_command(input);
+    let value = value.unwrap_or_default();
+    match name.to_lowercase().as_str() {
+        "move overhead" => {
+            if let Ok(overhead) = value.parse::<u64>() { options.move_overhead = overhead.min(5000); }
+        }
+        "multipv" => {
+            if let Ok(multi_pv) = value.parse::<usize>() { options.multi_pv = multi_pv.clamp(1, 64); }
+        }
+        "ponder" => { options.ponder = value.eq_ignore_ascii_case("true"); }
+        "evalprofile" => {
+            if let Some(profile) = EvalProfile::from_name(&value) {
+                options.eval_profile = profile;
+                load_eval_params(options);
+            }
+        }
+        "evalfile" => {
+            options.eval_file = if value == "<empty>" { String::new() } else { value };
+            load_eval_params(options);
+        }
+        "syzygypath" => {
+            options.syzygy_path = value.clone();
+            options.tablebase = None;
+            if value.is_empty() || value == "<empty>" { return; }
+            match Tablebases::load(&value, options.syzygy_probe_limit) {
+                Ok(tablebase) => {
+                    println!("info string Syzygy tables loaded for up to {} pieces", tablebase.max_pieces());
+                    options.tablebase = Some(Arc::new(tablebase));
+                }
+                Err(error) => println!("info string SyzygyPath not loaded: {}", error),
+            }
+        }
+        "syzygyprobelimit" => {
+            if let Ok(limit) = value.parse::<usize>() {
+                options.syzygy_probe_limit = limit.min(7);
+                if let Some(tablebase) = &options.tablebase { tablebase.set_probe_limit(options.syzygy_probe_limit); }
+            }
+        }
+        "ownbook" => { options.own_book = value.eq_ignore_ascii_case("true"); }
+        "bookfile" => {
+            options.book = None;
+            if value.is_empty() || value == "<empty>" { return; }
+            match OpeningBook::load(&value) {
+                Ok(book) => {
+                    println!("info string BookFile loaded with {} entries", book.entry_count());
+                    options.book = Some(Arc::new(book));
+                }
+                Err(error) => println!("info string BookFile not loaded: {}", error),
+            }
+        }
+        "bookdepth" => {
+            if let Ok(depth) = value.parse::<u32>() { options.book_depth = depth.min(100); }
+        }
+        "bestbookmove" => { options.best_book_move = value.eq_ignore_ascii_case("true"); }
+        // Internal option for reproducible regression runs, deliberately not advertised to GUIs
+        "deterministicnps" => {
+            if let Ok(nps) = value.parse::<u64>() { options.deterministic_nps = nps; }
+        }
+        _ => {}
+    }
+}
+
+// Rebuild the evaluation parameters from the profile, with the weights of EvalFile (if any) on top.
+// Weights missing from the file keep the values of the profile.
+fn load_eval_params(options: &mut EngineOptions) {
+    options.eval_params = options.eval_profile.params();
+    if options.eval_file.is_empty() { return; }
+    match options.eval_params.load_file(&options.eval_file) {
+        Ok(params) => options.eval_params = params,
+        Err(error) => println!("info string EvalFile not loaded: {}", error),
+    }
+}
+
+// Non-standard command: print the evaluation parameters in EvalFile format, or write them to a file
+pub fn dump_params(input: &str, options: &EngineOptions) {
+    let text = options.eval_params.to_text();
+    match input.split_whitespace().nth(1) {
+        Some(path) => {
+            if let Err(error) = fs::write(path, text) { println!("info string could not write {}: {}", path, error); }
+        }
+        None => print!("{}", text),
+    }
+}
+
+// Non-standard command: list the book moves of the current position with their weights
+pub fn book(board: &Chess, options: &EngineOptions) {
+    let Some(book) = &options.book else { println!("info string no BookFile loaded"); return; };
+    let moves = book.moves(board);
+    if moves.is_empty() { println!("No book moves for this position"); return; }
+
+    let total: u32 = moves.iter().map(|(_, weight)| *weight as u32).sum();
+    for (mv, weight) in moves {
+        println!("{:<6} weight {:>5} ({:.1}%)", mv.to_uci(CastlingMode::Standard).to_string(), weight, 100.0 * weight as f32 / total.max(1) as f32);
+    }
+}
+
+// Non-standard command: print every evaluation term for both sides of the current position
+pub fn eval(board: &Chess, options: &EngineOptions) {
+    let trace = trace_evaluation(board, &options.eval_params);
+    let white_to_move = board.turn() == Color::White;
+
+    println!("{:<18}|{:^15}|{:^15}|{:^22}", "Term", "White", "Black", "Total");
+    println!("{:<18}|{:>7}{:>7} |{:>7}{:>7} |{:>7}{:>7}{:>7} ", "", "MG", "EG", "MG", "EG", "MG", "EG", "Taper");
+    println!("{}", "-".repeat(75));
+    let mut total = Score::default();
+    for (name, player, opponent) in &trace.terms {
+        let (white, black) = if white_to_move { (*player, *opponent) } else { (*opponent, *player) };
+        let net = white - black;
+        total += net;
+        println!("{:<18}|{:>7}{:>7} |{:>7}{:>7} |{:>7}{:>7}{:>7} ", name, white.mg, white.eg, black.mg, black.eg, net.mg, net.eg, net.taper(trace.phase));
+    }
+    println!("{}", "-".repeat(75));
+    println!("{:<18}|{:>14} |{:>14} |{:>7}{:>7}{:>7} ", "Total", "", "", total.mg, total.eg, total.taper(trace.phase));
+
+    let side_score = trace.score;
+    let white_score = if white_to_move { side_score } else { -side_score };
+    println!("Game phase {}/24, endgame scale {}/{}", trace.phase, trace.scale, SCALE_NORMAL);
+    if let Some(endgame) = trace.endgame { println!("Specialised {} evaluation replaces the terms above", endgame); }
+    println!("Final evaluation: {} (White), {} (side to move)", white_score, side_score);
+}
+
 pub fn is_ready() {
     println!("readyok");
 }
@@ -43,18 +187,111 @@ pub fn set_position(input: &str, mut board: Chess) -> Chess {
     return board.to_owned();
 }
 
-pub fn go(input: &str, board: &Chess, t_table: &mut TranspositionTable) {
-    let (time, inc) = parse_go_command(input, board.turn().is_white());
-    let end_time: Instant = Instant::now() + Duration::from_millis((time/20+inc).max(1000));
-    let max_depth= 18;
-    let mut evaluate_count = 0;
-    //let start: Instant = Instant::now();
-    //let (best_move, best_score) = negamax(board, depth, ply, -i32::MAX, i32::MAX, &mut evaluate_count);
-    let (best_move, best_score, _max_depth) = iterative_deepening(board, max_depth, end_time, t_table, &mut evaluate_count);
-    //let time: u128 = start.elapsed().as_millis();
-    //println!("Searched {} nodes in {}ms ({}kn/sec)", evaluate_count, time, evaluate_count as f32 / time as f32);
-    println!("info score cp {}", best_score);
-    println!("bestmove {}", best_move.unwrap().to_uci(CastlingMode::Standard));
+// A search running in the background, so "stop" and "ponderhit" can be handled while it thinks
+pub struct SearchHandle {
+    thread: JoinHandle<Option<Move>>,
+    control: Arc<SearchControl>,
+}
+
+impl SearchHandle {
+    pub fn is_finished(&self) -> bool {
+        self.thread.is_finished()
+    }
+
+    // Wait for the search to send its best move and return it. A search that panicked has no move to report.
+    pub fn join(self) -> Option<Move> {
+        self.thread.join().unwrap_or_else(|_| {
+            println!("info string search failed");
+            None
+        })
+    }
+}
+
+pub fn go(input: &str, board: &Chess, t_table: &Arc<Mutex<TranspositionTable>>, options: &EngineOptions) -> SearchHandle {
+    let command = parse_go_command(input, board.turn().is_white());
+    let control = Arc::new(SearchControl::new(command.ponder));
+    let mut time_manager = TimeManager::new(command.time, command.inc, command.movestogo, options.move_overhead, control.clone());
+    if command.movetime.is_some() { time_manager.set_move_time(command.movetime); }
+    if command.infinite { time_manager.set_move_time(None); }
+    let has_clock = (command.time.is_some() || command.movetime.is_some()) && !command.infinite;
+    if let Some(nodes) = command.nodes { time_manager.set_node_limit(nodes as usize, has_clock); }
+    if options.deterministic_nps > 0 { time_manager.set_deterministic((options.deterministic_nps / 1000).max(1)); }
+
+    let (board, t_table, options, search_control) = (board.clone(), t_table.clone(), options.clone(), control.clone());
+    let thread = thread::spawn(move || {
+        let max_depth= 18;
+        let mut evaluate_count = 0;
+        // Play straight from the opening book while it still has moves for the position
+        let book_move = options.book.as_ref()
+            .filter(|_| options.own_book && board.fullmoves().get() <= options.book_depth)
+            .and_then(|book| book.choose(&board, options.best_book_move));
+
+        // The table stays locked only while searching, a previous search that panicked leaves it usable
+        let (best_move, best_score, max_depth_reached, ponder_move) = {
+            let mut t_table = t_table.lock().unwrap_or_else(PoisonError::into_inner);
+            // Entries left by earlier searches would change the result, so deterministic searches start from an empty table
+            if options.deterministic_nps > 0 { t_table.clear(); }
+            let (best_move, best_score, max_depth_reached) = match &book_move {
+                Some(book_move) => {
+                    println!("info string book move {}", book_move.to_uci(CastlingMode::Standard));
+                    (Some(book_move.clone()), 0, 0)
+                }
+                None => iterative_deepening(&board, max_depth, &mut time_manager, &mut t_table, &options.eval_params, options.tablebase.as_deref(), &mut evaluate_count, options.multi_pv,
+                    &mut |depth, lines, t_table, nodes| print_lines(&board, depth, lines, t_table, nodes, options.tablebase.as_ref().map_or(0, |tablebase| tablebase.hits()))),
+            };
+            // Suggest the expected reply from the principal variation so the GUI can let us ponder on it
+            let ponder_move = best_move.as_ref().and_then(|best_move| extract_pv(&board, best_move, &t_table, 2).get(1).cloned());
+            (best_move, best_score, max_depth_reached, ponder_move)
+        };
+
+        // UCI forbids sending bestmove while pondering or in an infinite search, so wait for "ponderhit" or "stop"
+        search_control.wait_for_bestmove(command.infinite);
+
+        let tb_hits = options.tablebase.as_ref().map_or(0, |tablebase| tablebase.hits());
+        if book_move.is_none() { println!("info depth {} score cp {} nodes {} tbhits {}", max_depth_reached, best_score, evaluate_count, tb_hits); }
+        match &best_move {
+            Some(best_move) => match ponder_move.filter(|_| options.ponder) {
+                Some(ponder_move) => println!("bestmove {} ponder {}", best_move.to_uci(CastlingMode::Standard), ponder_move.to_uci(CastlingMode::Standard)),
+                None => println!("bestmove {}", best_move.to_uci(CastlingMode::Standard)),
+            },
+            None => println!("bestmove 0000"), // No legal moves (checkmate or stalemate)
+        }
+        best_move
+    });
+
+    SearchHandle { thread, control }
+}
+
+// Report each MultiPV line of a completed iteration
+fn print_lines(board: &Chess, depth: usize, lines: &[Line], t_table: &TranspositionTable, evaluate_count: usize, tb_hits: u64) {
+    for (index, (line_move, score)) in lines.iter().enumerate() {
+        let pv: Vec<String> = extract_pv(board, line_move, t_table, depth)
+            .iter()
+            .map(|mv| mv.to_uci(CastlingMode::Standard).to_string())
+            .collect();
+        println!("info depth {} multipv {} score cp {} nodes {} tbhits {} pv {}", depth, index + 1, score, evaluate_count, tb_hits, pv.join(" "));
+    }
+}
+
+// Forget what was learned in the previous game
+pub fn new_game(search: &mut Option<SearchHandle>, t_table: &Arc<Mutex<TranspositionTable>>) {
+    stop(search);
+    t_table.lock().unwrap_or_else(PoisonError::into_inner).clear();
+}
+
+// Stop the running search (if any) and wait for it to report its best move
+pub fn stop(search: &mut Option<SearchHandle>) {
+    if let Some(handle) = search.take() {
+        handle.control.stop();
+        handle.join();
+    }
+}
+
+// The opponent played the move we were pondering on, continue as a normal timed search
+pub fn ponder_hit(search: &Option<SearchHandle>) {
+    if let Some(handle) = search {
+        handle.control.ponder_hit();
+    }
 }
 
 pub fn quit() { process::exit(0);}
@@ -0,0 +1,26 @@
+use chessai::annotate::{annotate_game, AnnotateSettings};
+use chessai::params::EvalParams;
+use chessai::pgn::{parse_pgn, write_game};
+
+#[test]
+fn blunders_are_marked_with_the_better_line() {
+    let mut game = parse_pgn("1. e4 e5 2. Qh5 Nc6 3. Bc4 Nf6?? 4. Qxf7# 1-0").remove(0).unwrap();
+    let settings = AnnotateSettings { depth: 3, ..AnnotateSettings::default() };
+    annotate_game(&mut game, &settings, &EvalParams::default(), None);
+
+    // Allowing mate in one is a blunder, and the "??" already in the game isn't repeated
+    let blunder = &game.moves[5];
+    assert_eq!(blunder.nags, vec![4]);
+    assert!(blunder.comment.as_deref().unwrap().starts_with("Blunder."));
+    assert_eq!(blunder.eval, Some(9999));
+    assert!(!blunder.variations[0].is_empty());
+
+    // The mating move ends the game, so it has no evaluation and nothing to criticise
+    assert_eq!(game.moves[6].eval, None);
+    assert!(game.moves[6].nags.is_empty());
+    assert!(game.moves.iter().all(|pgn_move| pgn_move.eval.is_some() || pgn_move.mv == game.moves[6].mv));
+
+    let text = write_game(&game);
+    assert!(text.contains("[Annotator \"je_bot depth 3\"]"));
+    assert!(text.contains("{[%eval #1] Blunder."));
+}
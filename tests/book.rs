@@ -0,0 +1,80 @@
+use chessai::book::{polyglot_key, OpeningBook};
+use chessai::bookgen::{BookBuilder, BookFilter};
+
+use shakmaty::{Chess, CastlingMode, fen::Fen, uci::UciMove};
+use std::env;
+use std::fs;
+
+fn parse(fen: &str) -> Chess {
+    let parsed: Fen = fen.parse().unwrap_or_else(|_| panic!("invalid FEN {}", fen));
+    parsed.into_position(CastlingMode::Standard).unwrap_or_else(|_| panic!("illegal position {}", fen))
+}
+
+fn uci(board: &Chess, mv: &str) -> shakmaty::Move {
+    mv.parse::<UciMove>().unwrap().to_move(board).unwrap()
+}
+
+#[test]
+fn polyglot_reference_keys() {
+    // The reference positions from the Polyglot book format specification
+    let positions = [
+        ("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1", 0x463b96181691fc9c),
+        ("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1", 0x823c9b50fd114196),
+        ("rnbqkbnr/ppp1pppp/8/3p4/4P3/8/PPPP1PPP/RNBQKBNR w KQkq d6 0 2", 0x0756b94461c50fb0),
+        ("rnbqkbnr/ppp1pppp/8/3pP3/8/8/PPPP1PPP/RNBQKBNR b KQkq - 0 2", 0x662fafb965db29d4),
+        ("rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3", 0x22a48b5a8e47ff78),
+        ("rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPPKPPP/RNBQ1BNR b kq - 0 3", 0x652a607ca3f242c1),
+        ("rnbq1bnr/ppp1pkpp/8/3pPp2/8/8/PPPPKPPP/RNBQ1BNR w - - 0 4", 0x00fdd303c946bdd9),
+        ("rnbqkbnr/p1pppppp/8/8/PpP4P/8/1P1PPPP1/RNBQKBNR b KQkq c3 0 3", 0x3c8123ea7b067637),
+        ("rnbqkbnr/p1pppppp/8/8/P6P/R1p5/1P1PPPP1/1NBQKBNR b Kkq - 0 4", 0x5c3f9b829b279560),
+    ];
+    for (fen, key) in positions {
+        assert_eq!(polyglot_key(&parse(fen)), key, "{}", fen);
+    }
+}
+
+#[test]
+fn book_moves_are_decoded() {
+    let board = parse("r3k2r/pppq1ppp/2n2n2/3pp3/3PP3/2N2N2/PPPQ1PPP/R3K2R w KQkq - 0 1");
+    let key = polyglot_key(&board);
+    // Castling short is stored as e1h1, then a pawn capture and a move that isn't legal here
+    let mut bytes = Vec::new();
+    for (mv, weight) in [(0x0107u16, 30u16), (0x06e4, 10), (0x0e1c, 50)] {
+        bytes.extend_from_slice(&key.to_be_bytes());
+        bytes.extend_from_slice(&mv.to_be_bytes());
+        bytes.extend_from_slice(&weight.to_be_bytes());
+        bytes.extend_from_slice(&0u32.to_be_bytes());
+    }
+    let path = env::temp_dir().join(format!("chessai-book-{}.bin", std::process::id()));
+    fs::write(&path, bytes).unwrap();
+    let book = OpeningBook::load(path.to_str().unwrap()).unwrap();
+    fs::remove_file(&path).unwrap();
+
+    let moves = book.moves(&board);
+    assert_eq!(moves, vec![(uci(&board, "e1g1"), 30), (uci(&board, "d4e5"), 10)]);
+    assert_eq!(book.choose(&board, true), Some(uci(&board, "e1g1")));
+    assert!(book.moves(&Chess::default()).is_empty());
+}
+
+#[test]
+fn bookgen_counts_mainline_results() {
+    let pgn = "[Result \"1-0\"]\n[WhiteElo \"2100\"]\n[BlackElo \"2000\"]\n\n1. e4 {comment} e5 (1... c5 2. Nf3) 2. Nf3! Nc6 $1 1-0\n\n\
+               [Result \"1/2-1/2\"]\n\n1. e4 c5 2. Nf3 1/2-1/2\n\n[Result \"*\"]\n\n1. d4 *\n";
+    let filter = BookFilter { max_plies: 3, min_elo: 0, results: vec!["1-0".into(), "0-1".into(), "1/2-1/2".into()], min_games: 1 };
+    let mut builder = BookBuilder::new();
+    builder.add_pgn(pgn, &filter);
+    assert_eq!((builder.games_used, builder.games_skipped), (2, 1));
+
+    // Two points for the win and one for the draw, the variation and the losing side's moves don't count
+    let start = Chess::default();
+    let entries = builder.entries(&filter);
+    let weights: Vec<u16> = entries.iter().filter(|entry| entry.0 == polyglot_key(&start)).map(|entry| entry.2).collect();
+    assert_eq!(weights, vec![3]);
+    assert_eq!(entries.len(), 4); // 1. e4, 2. Nf3 twice and 1... c5
+
+    // Only the first game is rated
+    let rated = BookFilter { min_elo: 2000, ..filter };
+    let mut builder = BookBuilder::new();
+    builder.add_pgn(pgn, &rated);
+    assert_eq!(builder.games_used, 1);
+}
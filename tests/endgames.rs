@@ -0,0 +1,51 @@
+use chessai::endgame::probe_endgame;
+use chessai::evaluate::evaluate_position;
+use chessai::params::EvalParams;
+
+use shakmaty::{Chess, CastlingMode, fen::Fen};
+
+fn parse(fen: &str) -> Chess {
+    let parsed: Fen = fen.parse().unwrap_or_else(|_| panic!("invalid FEN {}", fen));
+    parsed.into_position(CastlingMode::Standard).unwrap_or_else(|_| panic!("illegal position {}", fen))
+}
+
+fn score(fen: &str) -> i32 {
+    evaluate_position(&parse(fen), &EvalParams::default())
+}
+
+#[test]
+fn kpk_bitbase_results() {
+    // King on the 6th in front of its pawn wins whoever is to move
+    assert!(score("4k3/8/4K3/4P3/8/8/8/8 w - - 0 1") > 1000);
+    assert!(score("4k3/8/4K3/4P3/8/8/8/8 b - - 0 1") < -1000);
+    // The defending king holds the opposition in front of the pawn
+    assert_eq!(score("4k3/8/4P3/4K3/8/8/8/8 w - - 0 1"), 0);
+    // The pawn outruns the king
+    assert!(score("8/8/8/8/8/8/k6P/7K w - - 0 1") > 1000);
+    // Rook pawn with the defending king in the corner
+    assert_eq!(score("7k/8/8/8/8/8/7P/7K w - - 0 1"), 0);
+    // The same results with the colours reversed
+    assert!(score("8/8/8/8/4p3/4k3/8/4K3 b - - 0 1") > 1000);
+    assert_eq!(score("7k/7p/8/8/8/8/8/7K b - - 0 1"), 0);
+}
+
+#[test]
+fn dead_draws_score_level() {
+    for fen in ["8/8/4k3/8/8/4K3/8/8 w - - 0 1", "8/8/4k3/8/8/3BK3/8/8 w - - 0 1", "8/8/4k3/8/8/2NNK3/8/8 b - - 0 1"] {
+        assert_eq!(score(fen), 0, "{}", fen);
+    }
+}
+
+#[test]
+fn mating_nets_drive_the_king_to_the_right_corner() {
+    // KBNK with a light squared bishop, mate is only possible on a8 or h1
+    let params = EvalParams::default();
+    let (_, near_light_corner) = probe_endgame(&parse("k7/8/2K5/8/8/8/8/1B1N4 w - - 0 1"), &params).unwrap();
+    let (_, near_dark_corner) = probe_endgame(&parse("7k/8/5K2/8/8/8/8/1B1N4 w - - 0 1"), &params).unwrap();
+    assert!(near_light_corner > near_dark_corner);
+
+    // KRK, the defending king is better off in the centre
+    let (_, edge) = probe_endgame(&parse("3k4/8/3K4/8/8/8/8/R7 w - - 0 1"), &params).unwrap();
+    let (_, centre) = probe_endgame(&parse("8/8/8/3k4/8/3K4/8/R7 w - - 0 1"), &params).unwrap();
+    assert!(edge > centre);
+}
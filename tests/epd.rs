@@ -0,0 +1,44 @@
+use chessai::epd::parse_epd;
+
+use shakmaty::{Move, san::San};
+
+fn san(position: &chessai::epd::EpdPosition, san: &str) -> Move {
+    san.parse::<San>().unwrap().to_move(&position.board).unwrap()
+}
+
+#[test]
+fn best_and_avoid_moves() {
+    let position = parse_epd(r#"2rr3k/pp3pp1/1nnqbN1p/3pN3/2pP4/2P3Q1/PPB4P/R4RK1 w - - bm Qg6; id "WAC.001"; c0 "mate; in three";"#).unwrap();
+    assert_eq!(position.id, "WAC.001");
+    assert!(position.passes(&san(&position, "Qg6")));
+    assert!(!position.passes(&san(&position, "Nxf7+")));
+    // A plain comment in c0 isn't an STS score
+    assert_eq!(position.max_points(), 0);
+
+    // Fields separated by several spaces or tabs
+    let position = parse_epd("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR  w\tKQkq   -\tam a3 h3;").unwrap();
+    assert!(position.passes(&san(&position, "e4")));
+    assert!(!position.passes(&san(&position, "h3")));
+}
+
+#[test]
+fn sts_points() {
+    let position = parse_epd(r#"1k1r4/pp1b1R2/3q2pp/4p3/2B5/4Q3/PPP2B2/2K5 b - - id "STS(v1).001"; c0 "Qd1+=10, Qd2=3";"#).unwrap();
+    assert_eq!(position.max_points(), 10);
+    assert_eq!(position.points(&san(&position, "Qd2")), 3);
+    assert_eq!(position.points(&san(&position, "a6")), 0);
+    assert!(position.passes(&san(&position, "Qd1")));
+    assert!(!position.passes(&san(&position, "Qd2")));
+
+    // Promotions have an "=" before the points
+    let position = parse_epd(r#"8/4P1k1/8/8/8/8/8/4K3 w - - c0 "e8=Q=10, e8=N=2";"#).unwrap();
+    assert_eq!(position.points(&san(&position, "e8=Q")), 10);
+    assert_eq!(position.points(&san(&position, "e8=N")), 2);
+}
+
+#[test]
+fn invalid_records_are_rejected() {
+    assert!(parse_epd("8/8/8/8 w - -").is_err());
+    assert!(parse_epd("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - id \"no moves\";").is_err());
+    assert!(parse_epd("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - bm Qh5;").is_err());
+}
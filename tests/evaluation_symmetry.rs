@@ -0,0 +1,187 @@
+use chessai::endgame::probe_endgame;
+use chessai::evaluate::{evaluate_position, trace_evaluation, Score};
+use chessai::params::{EvalParams, EvalProfile};
+
+use std::fs;
+
+use shakmaty::{Chess, Position, CastlingMode, EnPassantMode, fen::Fen};
+
+// The same position with the board flipped vertically and the colours swapped
+fn mirror_fen(fen: &str) -> String {
+    let swap_case = |text: &str| -> String {
+        text.chars().map(|c| if c.is_ascii_uppercase() { c.to_ascii_lowercase() } else { c.to_ascii_uppercase() }).collect()
+    };
+    let fields: Vec<&str> = fen.split_whitespace().collect();
+
+    let placement: Vec<String> = fields[0].split('/').rev().map(swap_case).collect();
+    let turn = if fields[1] == "w" { "b" } else { "w" };
+    let castling = if fields[2] == "-" {
+        "-".to_string()
+    } else {
+        let swapped = swap_case(fields[2]);
+        "KQkq".chars().filter(|c| swapped.contains(*c)).collect()
+    };
+    let en_passant = if fields[3] == "-" {
+        "-".to_string()
+    } else {
+        let (file, rank) = fields[3].split_at(1);
+        format!("{}{}", file, if rank == "3" { "6" } else { "3" })
+    };
+    format!("{} {} {} {} {}", placement.join("/"), turn, castling, en_passant, fields[4..].join(" "))
+}
+
+// The same board with the other side to move. En passant is dropped as it can't carry over.
+fn pass_turn(fen: &str) -> String {
+    let fields: Vec<&str> = fen.split_whitespace().collect();
+    let turn = if fields[1] == "w" { "b" } else { "w" };
+    format!("{} {} {} - {}", fields[0], turn, fields[2], fields[4..].join(" "))
+}
+
+fn try_parse(fen: &str) -> Option<Chess> {
+    let parsed: Fen = fen.parse().ok()?;
+    parsed.into_position(CastlingMode::Standard).ok()
+}
+
+fn parse(fen: &str) -> Chess {
+    let parsed: Fen = fen.parse().unwrap_or_else(|_| panic!("invalid FEN {}", fen));
+    parsed.into_position(CastlingMode::Standard).unwrap_or_else(|_| panic!("illegal position {}", fen))
+}
+
+// Deterministic pseudo-random numbers so the corpus is identical on every run
+struct XorShift(u64);
+
+impl XorShift {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+}
+
+// The hand-picked positions plus every position reached in a set of random games
+fn corpus() -> Vec<String> {
+    let path = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/data/symmetry.fen");
+    let mut fens: Vec<String> = fs::read_to_string(path).unwrap()
+        .lines()
+        .map(|line| line.trim())
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| line.to_string())
+        .collect();
+
+    let mut rng = XorShift(0x9E37_79B9_7F4A_7C15);
+    for _ in 0..100 {
+        let mut board = Chess::default();
+        for _ in 0..120 {
+            let moves = board.legal_moves();
+            if moves.is_empty() { break; }
+            let mv = moves[(rng.next() % moves.len() as u64) as usize].clone();
+            board.play_unchecked(&mv);
+            fens.push(Fen::from_position(board.clone(), EnPassantMode::Legal).to_string());
+        }
+    }
+    fens
+}
+
+#[test]
+fn mirror_fen_round_trips() {
+    for fen in corpus() {
+        assert_eq!(mirror_fen(&mirror_fen(&fen)), fen);
+    }
+}
+
+#[test]
+fn evaluation_is_colour_symmetric() {
+    for profile in EvalProfile::ALL {
+        let params: EvalParams = profile.params();
+        for fen in corpus() {
+            let mirrored = mirror_fen(&fen);
+            assert_eq!(
+                evaluate_position(&parse(&fen), &params),
+                evaluate_position(&parse(&mirrored), &params),
+                "{} profile: {} and {} evaluate differently", profile.name(), fen, mirrored,
+            );
+        }
+    }
+}
+
+#[test]
+fn every_term_is_colour_symmetric() {
+    let params = EvalParams::default();
+    for fen in corpus() {
+        let mirrored = mirror_fen(&fen);
+        let trace = trace_evaluation(&parse(&fen), &params);
+        let mirrored_trace = trace_evaluation(&parse(&mirrored), &params);
+        for (term, mirrored_term) in trace.terms.iter().zip(mirrored_trace.terms.iter()) {
+            assert_eq!(term, mirrored_term, "{} differs between {} and {}", term.0, fen, mirrored);
+        }
+    }
+}
+
+// The mirror tests can't see a term that treats the side to move differently from its opponent,
+// since flipping the colours also flips the side to move. Without a tempo term, passing the move
+// on the same board must simply negate the evaluation. The specialised endgame evaluators are
+// exact about who is to move (e.g. KPK), so those positions are left to the mirror tests.
+#[test]
+fn evaluation_is_independent_of_side_to_move() {
+    for profile in EvalProfile::ALL {
+        let params: EvalParams = profile.params();
+        for fen in corpus() {
+            let passed = pass_turn(&fen);
+            // Skip positions where the side that just moved would be left in check
+            let Some(passed_board) = try_parse(&passed) else { continue; };
+            if probe_endgame(&passed_board, &params).is_some() { continue; }
+            assert_eq!(
+                evaluate_position(&parse(&fen), &params),
+                -evaluate_position(&passed_board, &params),
+                "{} profile: {} and {} don't evaluate to opposite scores", profile.name(), fen, passed,
+            );
+        }
+    }
+}
+
+#[test]
+fn every_term_is_independent_of_side_to_move() {
+    let params = EvalParams::default();
+    for fen in corpus() {
+        let passed = pass_turn(&fen);
+        let Some(passed_board) = try_parse(&passed) else { continue; };
+        if probe_endgame(&passed_board, &params).is_some() { continue; }
+        let trace = trace_evaluation(&parse(&fen), &params);
+        let passed_trace = trace_evaluation(&passed_board, &params);
+        for (term, passed_term) in trace.terms.iter().zip(passed_trace.terms.iter()) {
+            assert_eq!((term.1, term.2), (passed_term.2, passed_term.1), "{} differs between {} and {}", term.0, fen, passed);
+        }
+    }
+}
+
+#[test]
+fn piece_square_tables_keep_the_file() {
+    // The Simplified queen table gives c2 a bonus but not f2, which is where a rotated board would read c2 from
+    let queens = |fen: &str| {
+        let trace = trace_evaluation(&parse(fen), &EvalParams::default());
+        let (_, player, opponent) = trace.terms.into_iter().find(|(name, _, _)| *name == "PST queens").unwrap();
+        (player.mg, opponent.mg)
+    };
+    assert_eq!(queens("4k3/8/8/8/8/8/2Q5/4K3 w - - 0 1"), (5, 0));
+    assert_eq!(queens("4k3/8/8/8/8/8/5Q2/4K3 w - - 0 1"), (0, 0));
+    // Black reads the table from its own side
+    assert_eq!(queens("4k3/2q5/8/8/8/8/8/4K3 w - - 0 1"), (0, 5));
+    assert_eq!(queens("4k3/5q2/8/8/8/8/8/4K3 w - - 0 1"), (0, 0));
+}
+
+#[test]
+fn semi_open_files_need_a_rook() {
+    let params = EvalParams::default();
+    let open_files = |fen: &str| {
+        let trace = trace_evaluation(&parse(fen), &params);
+        let (_, player, opponent) = trace.terms.into_iter().find(|(name, _, _)| *name == "Open files").unwrap();
+        (player, opponent)
+    };
+    // The rook on the a-file faces only a black pawn
+    assert_eq!(open_files("4k3/p7/8/8/8/8/8/R3K3 w - - 0 1"), (params.semi_open_file_bonus, Score::default()));
+    // The a-file is still semi-open, but only the rook on the open h-file scores
+    assert_eq!(open_files("4k3/p7/8/8/8/8/8/4K2R w - - 0 1"), (params.open_file_bonus, Score::default()));
+    // Black's rook on the b-file has only a white pawn in front of it
+    assert_eq!(open_files("1r2k3/8/8/8/8/8/1P6/4K3 w - - 0 1"), (Score::default(), params.semi_open_file_bonus));
+}
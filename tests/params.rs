@@ -0,0 +1,27 @@
+use chessai::params::{EvalParams, EvalProfile};
+
+#[test]
+fn text_round_trips_for_every_profile() {
+    for profile in EvalProfile::ALL {
+        let params = profile.params();
+        // Read on top of a different profile, so every value has to come from the text
+        let other = EvalProfile::ALL.into_iter().find(|other| *other != profile).unwrap().params();
+        let parsed = other.from_text(&params.to_text()).unwrap();
+        assert_eq!(parsed.to_vector(), params.to_vector(), "{}", profile.name());
+        assert_eq!(parsed.to_text(), params.to_text());
+    }
+}
+
+#[test]
+fn partial_and_invalid_text() {
+    let params = EvalParams::default();
+    // Missing weights keep their current value
+    let parsed = params.from_text("# only the bishop pair\nbishop_pair_bonus = [40, 60]\n").unwrap();
+    assert_eq!((parsed.bishop_pair_bonus.mg, parsed.bishop_pair_bonus.eg), (40, 60));
+    assert_eq!(parsed.piece_values, params.piece_values);
+
+    assert!(matches!(params.from_text("bishop_pair = [40, 60]"), Err(error) if error.contains("unknown parameter bishop_pair")));
+    assert!(matches!(params.from_text("piece_values = [100, 300, 300, 500, 900]"), Err(error) if error.contains("expected 6")));
+    assert!(params.from_text("bishop_pair_bonus = [40, x]").is_err());
+    assert!(params.from_text("bishop_pair_bonus 40").is_err());
+}
@@ -0,0 +1,19 @@
+use chessai::parsers::parse_go_command;
+
+#[test]
+fn go_command_clocks_and_flags() {
+    let command = parse_go_command("go wtime 60000 btime -350 winc 1000 binc 1000 movestogo 12", false);
+    // An overdrawn clock counts as no time left
+    assert_eq!(command.time, Some(0));
+    assert_eq!(command.inc, 1000);
+    assert_eq!(command.movestogo, Some(12));
+    assert!(!command.ponder && !command.infinite);
+
+    let command = parse_go_command("go movetime 2500 nodes 100000", true);
+    assert_eq!((command.time, command.movetime, command.nodes), (None, Some(2500), Some(100000)));
+
+    let command = parse_go_command("go infinite", true);
+    assert!(command.infinite);
+    let command = parse_go_command("go ponder wtime 1000 btime 1000", true);
+    assert!(command.ponder && command.time == Some(1000));
+}
@@ -0,0 +1,93 @@
+use chessai::pgn::{parse_pgn, write_game, parse_eval, format_eval, Game, PgnMove};
+
+use shakmaty::{Chess, Position, san::San};
+
+const GAME: &str = r#"[Event "Club match"]
+[White "je_bot"]
+[Black "Opponent"]
+[Result "1-0"]
+
+{Opening comment} 1. e4 {[%eval 0.30] King's pawn} e5 (1... c5 $2 {Sicilian} 2. Nf3 (2. c3)) 2. Nf3!? Nc6
+; rest of line comment
+3. Bc4 Nd4?? 4. Nxe5 Qg5 5. Nxf7 Qxg2 6. Rf1 Qxe4+ 7. Be2 Nf3# 1-0
+
+[Event "Second"]
+[Result "*"]
+
+1. d4 Qh5 *
+"#;
+
+#[test]
+fn parses_tags_comments_nags_and_variations() {
+    let games = parse_pgn(GAME);
+    assert_eq!(games.len(), 2);
+    let game = games[0].as_ref().unwrap();
+
+    assert_eq!(game.tag("White"), Some("je_bot"));
+    assert_eq!(game.result(), "1-0");
+    assert_eq!(game.moves.len(), 14);
+    assert_eq!(game.moves[0].comment_before.as_deref(), Some("Opening comment"));
+    assert_eq!(game.moves[0].comment.as_deref(), Some("King's pawn"));
+    assert_eq!(game.moves[0].eval, Some(30));
+    assert_eq!(game.moves[2].nags, vec![5]);
+    assert_eq!(game.moves[3].comment.as_deref(), Some("rest of line comment"));
+    assert_eq!(game.moves[5].nags, vec![4]);
+
+    // The variation replaces 1... e5 and has its own variation on White's second move
+    let variation = &game.moves[1].variations[0];
+    assert_eq!(variation.len(), 2);
+    assert_eq!(variation[0].nags, vec![2]);
+    assert_eq!(variation[0].comment.as_deref(), Some("Sicilian"));
+    assert_eq!(variation[1].variations.len(), 1);
+
+    assert!(game.positions().last().unwrap().is_checkmate());
+
+    // The second game has an illegal move, which doesn't stop the first from being read
+    assert!(matches!(&games[1], Err(error) if error.contains("Qh5")));
+}
+
+#[test]
+fn castling_written_with_zeros() {
+    let game = parse_pgn("1. e4 e5 2. Nf3 Nc6 3. Bc4 Bc5 4.0-0 d6 5. d3 Be6 6. Nc3 Qd7 7. Be3 0-0-0!? *").remove(0).unwrap();
+    assert_eq!(game.moves.len(), 14);
+    assert_eq!(San::from_move(&game.positions()[6], &game.moves[6].mv).to_string(), "O-O");
+    assert_eq!(San::from_move(&game.positions()[13], &game.moves[13].mv).to_string(), "O-O-O");
+    assert_eq!(game.moves[13].nags, vec![5]);
+}
+
+#[test]
+fn written_games_parse_back_the_same() {
+    let game = parse_pgn(GAME).remove(0).unwrap();
+    let text = write_game(&game);
+    assert!(text.starts_with("[Event \"Club match\"]\n"));
+    let movetext = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    assert!(movetext.contains("{Opening comment} 1. e4 {[%eval 0.30] King's pawn} 1... e5 (1... c5 $2 {Sicilian} 2. Nf3 (2. c3)) 2. Nf3 $5 Nc6"));
+    assert!(movetext.contains("7. Be2 Nf3# 1-0"));
+    assert!(text.lines().all(|line| line.len() <= 80));
+    assert_eq!(write_game(&parse_pgn(&text).remove(0).unwrap()), text);
+}
+
+#[test]
+fn new_games_record_the_start_position() {
+    let mut game = Game::new(Chess::default());
+    assert_eq!(game.tag("FEN"), None);
+    let board = Chess::default();
+    let mut pgn_move = PgnMove::new("Nf3".parse::<San>().unwrap().to_move(&board).unwrap());
+    pgn_move.eval = Some(-9995);
+    game.moves.push(pgn_move);
+    assert!(write_game(&game).contains("1. Nf3 {[%eval #-3]} *"));
+
+    let after = game.positions().pop().unwrap();
+    assert_eq!(Game::new(after).tag("FEN"), Some("rnbqkbnr/pppppppp/8/8/8/5N2/PPPPPPPP/RNBQKB1R b KQkq - 1 1"));
+}
+
+#[test]
+fn eval_commands_round_trip() {
+    for score in [0, 35, -120, 9999, 9995, -9995, -9999] {
+        assert_eq!(parse_eval(&format_eval(score)), Some(score));
+    }
+    assert_eq!(format_eval(9999), "#1");
+    assert_eq!(format_eval(-9999), "#-1");
+    assert_eq!(format_eval(-9998), "#-1");
+    assert_eq!(parse_eval("-1.5"), Some(-150));
+}
@@ -0,0 +1,102 @@
+use chessai::params::EvalParams;
+use chessai::search::{iterative_deepening, Line};
+use chessai::timeman::{TimeManager, SearchControl};
+use chessai::transposition::TranspositionTable;
+
+use shakmaty::{Chess, CastlingMode, Move, Position, fen::Fen};
+use std::sync::Arc;
+
+fn parse(fen: &str) -> Chess {
+    let parsed: Fen = fen.parse().unwrap_or_else(|_| panic!("invalid FEN {}", fen));
+    parsed.into_position(CastlingMode::Standard).unwrap_or_else(|_| panic!("illegal position {}", fen))
+}
+
+// Search with the given time manager, returning the best move, its score, the depth reached and the lines of every iteration
+fn search(board: &Chess, max_depth: usize, time_manager: &mut TimeManager, multi_pv: usize) -> (Option<Move>, i32, usize, Vec<Vec<Line>>) {
+    let mut t_table = TranspositionTable::new();
+    let mut evaluate_count = 0;
+    let mut iterations: Vec<Vec<Line>> = Vec::new();
+    let (best_move, score, depth) = iterative_deepening(board, max_depth, time_manager, &mut t_table, &EvalParams::default(), None, &mut evaluate_count, multi_pv,
+        &mut |_, lines, _, _| iterations.push(lines.to_vec()));
+    (best_move, score, depth, iterations)
+}
+
+fn timed(move_time: u64) -> TimeManager {
+    let mut time_manager = TimeManager::new(None, 0, None, 0, Arc::new(SearchControl::new(false)));
+    time_manager.set_move_time(Some(move_time));
+    time_manager
+}
+
+// A 2 second clock measured at 20 nodes per millisecond
+fn deterministic() -> TimeManager {
+    let mut time_manager = TimeManager::new(Some(2_000), 0, None, 0, Arc::new(SearchControl::new(false)));
+    time_manager.set_deterministic(20);
+    time_manager
+}
+
+fn untimed() -> TimeManager {
+    let mut time_manager = TimeManager::new(None, 0, None, 0, Arc::new(SearchControl::new(false)));
+    time_manager.set_move_time(None);
+    time_manager
+}
+
+#[test]
+fn out_of_time_still_plays_a_legal_move() {
+    let board = parse("r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3");
+    let (best_move, _, depth, iterations) = search(&board, 64, &mut timed(0), 1);
+    assert!(board.legal_moves().contains(&best_move.unwrap()));
+    assert_eq!(depth, 0);
+    assert!(iterations.is_empty());
+}
+
+#[test]
+fn no_move_when_the_game_is_over() {
+    // Fool's mate
+    let board = parse("rnb1kbnr/pppp1ppp/8/4p3/6Pq/5P2/PPPPP2P/RNBQKBNR w KQkq - 1 3");
+    let (best_move, score, depth, _) = search(&board, 64, &mut timed(1000), 1);
+    assert_eq!((best_move, depth), (None, 0));
+    assert_eq!(score, -10_000);
+
+    let stalemate = parse("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1");
+    let (best_move, score, _, _) = search(&stalemate, 64, &mut timed(1000), 1);
+    assert!(best_move.is_none() && score <= 0 && score > -1000);
+}
+
+#[test]
+fn multi_pv_lines_are_distinct_and_ordered() {
+    let board = parse("r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3");
+    let (best_move, score, depth, iterations) = search(&board, 3, &mut untimed(), 4);
+    assert_eq!((depth, iterations.len()), (3, 3));
+    for lines in &iterations {
+        assert_eq!(lines.len(), 4);
+        for (index, (line_move, line_score)) in lines.iter().enumerate() {
+            assert!(!lines[..index].iter().any(|(other, _)| other == line_move));
+            if index > 0 { assert!(*line_score <= lines[index - 1].1); }
+        }
+    }
+    // The first line is the move that gets played
+    assert_eq!(iterations.last().unwrap()[0], (best_move.unwrap(), score));
+
+    // There can't be more lines than legal moves
+    let board = parse("7k/8/8/8/8/8/8/K6R b - - 0 1");
+    let (_, _, _, iterations) = search(&board, 2, &mut untimed(), 5);
+    assert_eq!(iterations.len(), 2);
+    assert!(iterations.iter().all(|lines| lines.len() == 2));
+}
+
+#[test]
+fn node_clock_searches_are_repeatable() {
+    let board = parse("r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3");
+    let (best_move, score, depth, _) = search(&board, 64, &mut deterministic(), 1);
+    assert!(best_move.is_some() && depth > 0);
+    assert_eq!(search(&board, 64, &mut deterministic(), 1).0, best_move);
+    assert_eq!(search(&board, 64, &mut deterministic(), 1).1, score);
+
+    // A table filled by another search gives the same result once it is cleared
+    let mut t_table = TranspositionTable::new();
+    let other = parse("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1");
+    iterative_deepening(&other, 64, &mut deterministic(), &mut t_table, &EvalParams::default(), None, &mut 0, 1, &mut |_, _, _, _| {});
+    t_table.clear();
+    let again = iterative_deepening(&board, 64, &mut deterministic(), &mut t_table, &EvalParams::default(), None, &mut 0, 1, &mut |_, _, _, _| {});
+    assert_eq!(again, (best_move, score, depth));
+}
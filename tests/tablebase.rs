@@ -0,0 +1,94 @@
+use chessai::params::EvalParams;
+use chessai::search::fixed_search;
+use chessai::tablebase::{Tablebases, TB_WIN};
+use chessai::transposition::TranspositionTable;
+
+use shakmaty::{Chess, CastlingMode, Position, fen::Fen, uci::UciMove};
+
+// The KQvK and KRvK tables, small enough to keep with the tests
+const TABLES: &str = "tests/data/syzygy";
+
+fn parse(fen: &str) -> Chess {
+    let parsed: Fen = fen.parse().unwrap_or_else(|_| panic!("invalid FEN {}", fen));
+    parsed.into_position(CastlingMode::Standard).unwrap_or_else(|_| panic!("illegal position {}", fen))
+}
+
+fn uci(board: &Chess, mv: &str) -> shakmaty::Move {
+    mv.parse::<UciMove>().unwrap().to_move(board).unwrap()
+}
+
+#[test]
+fn wdl_scores_and_probe_limit() {
+    let tablebase = Tablebases::load(TABLES, 7).unwrap();
+    assert_eq!(tablebase.max_pieces(), 3);
+
+    assert_eq!(tablebase.probe_wdl(&parse("4k3/8/8/8/8/8/8/3QK3 w - - 0 1"), 2), Some(TB_WIN - 2));
+    assert_eq!(tablebase.probe_wdl(&parse("4k3/8/8/8/8/8/8/3QK3 b - - 0 1"), 3), Some(-TB_WIN + 3));
+    assert_eq!(tablebase.probe_wdl(&parse("4k3/8/8/8/8/8/8/R3K3 b - - 0 1"), 1), Some(-TB_WIN + 1));
+    // The rook is lost straight away
+    assert_eq!(tablebase.probe_wdl(&parse("8/8/8/8/8/8/1k6/R3K3 b - - 0 1"), 1), Some(0));
+    assert_eq!(tablebase.hits(), 4);
+
+    // WDL results are only trusted right after a zeroing move, and missing tables or the probe limit fall back to the search
+    assert_eq!(tablebase.probe_wdl(&parse("4k3/8/8/8/8/8/8/3QK3 w - - 5 10"), 0), None);
+    assert_eq!(tablebase.probe_wdl(&parse("4k3/8/8/8/8/8/8/2BNK3 w - - 0 1"), 0), None);
+    tablebase.set_probe_limit(2);
+    assert_eq!(tablebase.probe_wdl(&parse("4k3/8/8/8/8/8/8/3QK3 w - - 0 1"), 0), None);
+    assert_eq!(tablebase.hits(), 4);
+}
+
+#[test]
+fn root_moves_keep_the_fastest_win() {
+    let tablebase = Tablebases::load(TABLES, 7).unwrap();
+
+    // Only the mate is left to the search
+    let board = parse("7k/8/6K1/8/8/8/8/1Q6 w - - 0 1");
+    let excluded = tablebase.excluded_root_moves(&board);
+    assert_eq!(excluded.len(), board.legal_moves().len() - 1);
+    assert!(!excluded.contains(&uci(&board, "b1b8")));
+    assert_eq!(tablebase.hits(), board.legal_moves().len() as u64);
+
+    // Moves that give the rook away are excluded, moves that lose time may be
+    let board = parse("8/8/8/4k3/8/8/8/3RK3 w - - 0 1");
+    let excluded = tablebase.excluded_root_moves(&board);
+    assert!(excluded.contains(&uci(&board, "d1d5")));
+    assert!(excluded.len() < board.legal_moves().len());
+
+    // The defending side keeps the moves that lose slowest
+    let board = parse("8/8/8/4k3/8/8/8/R3K3 b - - 0 1");
+    assert!(!tablebase.excluded_root_moves(&board).is_empty());
+
+    tablebase.reset_hits();
+    assert_eq!(tablebase.hits(), 0);
+}
+
+#[test]
+fn fifty_move_rule_draws_slow_wins() {
+    let tablebase = Tablebases::load(TABLES, 7).unwrap();
+
+    // One move before the fifty move rule no win can be completed, so keeping the rook is as much a draw as giving it away
+    let board = parse("8/8/8/4k3/8/8/8/R3K3 w - - 99 80");
+    assert!(tablebase.excluded_root_moves(&board).is_empty());
+
+    // A mate still ends the game in time
+    let board = parse("7k/8/6K1/8/8/8/8/1Q6 w - - 99 80");
+    assert_eq!(tablebase.excluded_root_moves(&board).len(), board.legal_moves().len() - 1);
+}
+
+#[test]
+fn search_plays_a_tablebase_move() {
+    let tablebase = Tablebases::load(TABLES, 7).unwrap();
+    let board = parse("8/8/8/4k3/8/8/8/R3K3 w - - 0 1");
+    let excluded = tablebase.excluded_root_moves(&board);
+
+    let mut t_table = TranspositionTable::new();
+    let (best_move, score, _) = fixed_search(&board, 4, None, &mut t_table, &EvalParams::default(), Some(&tablebase));
+    let best_move = best_move.unwrap();
+    assert!(!excluded.contains(&best_move));
+    assert!(score > 1000);
+    assert!(tablebase.hits() > 0);
+
+    // A second search of the same position starts from the root entry left in the transposition table
+    let (again, _, _) = fixed_search(&board, 4, None, &mut t_table, &EvalParams::default(), Some(&tablebase));
+    assert!(!excluded.contains(&again.unwrap()));
+}
@@ -0,0 +1,119 @@
+use chessai::timeman::{TimeManager, SearchControl};
+
+use std::sync::Arc;
+use std::time::Duration;
+
+// A node clock at one node per millisecond, so node counts read as milliseconds and nothing depends on the wall clock
+fn clock(time: u64, inc: u64, movestogo: Option<u64>, move_overhead: u64) -> TimeManager {
+    let mut time_manager = TimeManager::new(Some(time), inc, movestogo, move_overhead, Arc::new(SearchControl::new(false)));
+    time_manager.set_deterministic(1);
+    time_manager
+}
+
+// The first node count (in milliseconds) at which the condition holds
+fn first(condition: impl Fn(usize) -> bool) -> usize {
+    let (mut low, mut high) = (0, 1_000_000_000);
+    while low < high {
+        let middle = (low + high) / 2;
+        if condition(middle) { high = middle; } else { low = middle + 1; }
+    }
+    low
+}
+
+// Milliseconds after which no new iteration starts, and after which the search in progress is aborted
+fn soft(time_manager: &TimeManager) -> usize {
+    first(|nodes| time_manager.should_stop(nodes, Duration::ZERO))
+}
+
+fn hard(time_manager: &TimeManager) -> usize {
+    let limits = time_manager.limits();
+    first(|nodes| limits.expired(nodes))
+}
+
+#[test]
+fn soft_and_hard_split() {
+    // 60s split over the default 20 moves, with the hard limit four times the soft limit
+    let time_manager = clock(60_000, 0, None, 0);
+    assert_eq!((soft(&time_manager), hard(&time_manager)), (3000, 12_000));
+
+    // The overhead is never planned for, and three quarters of the increment is added
+    let time_manager = clock(30_100, 1000, Some(30), 100);
+    assert_eq!((soft(&time_manager), hard(&time_manager)), (1750, 7000));
+
+    // Few moves to go, so the hard limit is capped at three quarters of the available time
+    let time_manager = clock(60_100, 1000, Some(5), 100);
+    assert_eq!((soft(&time_manager), hard(&time_manager)), (12_750, 45_000));
+
+    // An iteration that can't finish before the hard limit isn't started
+    assert!(time_manager.should_stop(10_000, Duration::from_millis(20_000)));
+    assert!(!time_manager.should_stop(10_000, Duration::from_millis(8000)));
+}
+
+#[test]
+fn hard_limit_never_exceeds_the_available_time() {
+    for time in [1000, 1500, 5000, 20_000, 60_000, 300_000, 3_600_000] {
+        for inc in [0, 100, 2000, 30_000] {
+            for movestogo in [None, Some(1), Some(2), Some(10), Some(40), Some(100)] {
+                let available = time - 30;
+                let time_manager = clock(time, inc, movestogo, 30);
+                let (soft, hard) = (soft(&time_manager), hard(&time_manager));
+                let cap = if available < 1000 { available / 2 } else { available * 3 / 4 };
+                assert!(hard as u64 <= cap, "time {} inc {} movestogo {:?}: hard {}", time, inc, movestogo, hard);
+                assert!(soft <= hard);
+            }
+        }
+    }
+}
+
+#[test]
+fn emergency_allocation() {
+    // Below a second, play almost at once and lean on the increment
+    let time_manager = clock(800, 200, None, 0);
+    assert_eq!((soft(&time_manager), hard(&time_manager)), (70, 180));
+
+    // Without an increment the hard limit stays under half of what is left
+    let time_manager = clock(900, 0, Some(1), 0);
+    assert_eq!((soft(&time_manager), hard(&time_manager)), (22, 90));
+
+    // An empty (or overdrawn) clock stops straight away
+    let time_manager = clock(0, 0, None, 0);
+    assert_eq!((soft(&time_manager), hard(&time_manager)), (0, 0));
+}
+
+#[test]
+fn unstable_searches_get_more_time() {
+    let mut time_manager = clock(60_000, 0, None, 0);
+    let close = |actual: usize, expected: usize| actual.abs_diff(expected) <= 1;
+
+    // A new best move stretches the soft limit by 1.3, a falling score by 1.2
+    time_manager.update(true, 0, 0);
+    assert!(close(soft(&time_manager), 3900));
+    time_manager.update(false, 0, -100);
+    assert!(close(soft(&time_manager), 4680));
+    time_manager.update(true, 0, -100);
+    assert!(close(soft(&time_manager), 7300));
+
+    // The extension is capped at 2.5
+    time_manager.update(true, 0, -100);
+    assert!(close(soft(&time_manager), 7500));
+
+    // Stable iterations decay it by 0.9, down to the base allocation
+    time_manager.update(false, 0, 0);
+    assert!(close(soft(&time_manager), 6750));
+    for _ in 0..20 { time_manager.update(false, 0, 0); }
+    assert_eq!(soft(&time_manager), 3000);
+
+    // The hard limit is never stretched
+    assert_eq!(hard(&time_manager), 12_000);
+}
+
+#[test]
+fn movetime_and_infinite() {
+    let mut time_manager = clock(60_000, 0, None, 0);
+    time_manager.set_move_time(Some(2500));
+    assert_eq!((soft(&time_manager), hard(&time_manager)), (2500, 2500));
+
+    time_manager.set_move_time(None);
+    assert!(!time_manager.should_stop(100_000_000, Duration::ZERO));
+    assert!(!time_manager.limits().expired(100_000_000));
+}
@@ -0,0 +1,51 @@
+use chessai::params::EvalParams;
+use chessai::tuner::{load_positions, find_k, local_search, mean_squared_error};
+
+use std::env;
+use std::fs;
+
+const POSITIONS: &str = "\
+# White a queen up, Black a rook up, and level positions
+3qk3/8/8/8/8/8/8/3QK2Q w - - 0 1 1-0
+4k3/8/8/8/8/8/8/Q3K3 b - - 0 1 [1.0]
+r3k3/8/8/8/8/8/8/4K3 w - - 0 1 0-1
+rr2k3/pppp4/8/8/8/8/PPPP4/R3K3 b - - 0 1 [0.0]
+rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1 1/2-1/2
+4k3/4p3/8/8/8/8/4P3/4K3 w - - 0 1 [0.5]
+";
+
+fn write(name: &str, text: &str) -> String {
+    let path = env::temp_dir().join(format!("chessai-tuner-{}-{}.txt", name, std::process::id()));
+    fs::write(&path, text).unwrap();
+    path.to_string_lossy().to_string()
+}
+
+#[test]
+fn fit_and_one_pass_of_local_search() {
+    let params = EvalParams::default();
+    let path = write("positions", POSITIONS);
+    let positions = load_positions(&path, &params).unwrap();
+    fs::remove_file(&path).unwrap();
+    assert_eq!(positions.len(), 6);
+    assert_eq!(positions.iter().map(|position| position.result).collect::<Vec<_>>(), vec![1.0, 1.0, 0.0, 0.0, 0.5, 0.5]);
+
+    // The fitted constant is no worse than the starting guess of 1.0
+    let k = find_k(&positions, &params);
+    assert!(k > 0.0);
+    let error = mean_squared_error(&positions, &params, k);
+    assert!(error <= mean_squared_error(&positions, &params, 1.0));
+
+    let mut passes = Vec::new();
+    let tuned = local_search(&positions, &params, k, 1, |pass, pass_error, _| passes.push((pass, pass_error)));
+    assert_eq!(passes.len(), 1);
+    assert!(passes[0].1 <= error);
+    assert!(mean_squared_error(&positions, &tuned, k) <= error);
+}
+
+#[test]
+fn epd_operations_are_rejected() {
+    let path = write("epd", "3qk3/8/8/8/8/8/8/3QK2Q w - - c9 \"1-0\";\n");
+    let result = load_positions(&path, &EvalParams::default());
+    fs::remove_file(&path).unwrap();
+    assert!(matches!(result, Err(error) if error.contains("line 1: EPD operations are not supported")));
+}
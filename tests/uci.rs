@@ -0,0 +1,40 @@
+use chessai::options::EngineOptions;
+use chessai::transposition::TranspositionTable;
+use chessai::uci::{go, ponder_hit, stop, set_position};
+
+use shakmaty::{Chess, CastlingMode, Position, fen::Fen};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+#[test]
+fn ponder_then_ponderhit_sends_a_move() {
+    let board = set_position("position startpos moves e2e4", Chess::default());
+    let t_table = Arc::new(Mutex::new(TranspositionTable::new()));
+    let mut search = Some(go("go ponder wtime 500 btime 500", &board, &t_table, &EngineOptions::default()));
+
+    // Pondering never ends on its own, whatever the clock says
+    thread::sleep(Duration::from_millis(700));
+    assert!(!search.as_ref().unwrap().is_finished());
+
+    // After ponderhit the search runs on the clock and sends its move without a "stop"
+    ponder_hit(&search);
+    let best_move = search.take().unwrap().join();
+    assert!(board.legal_moves().contains(&best_move.unwrap()));
+}
+
+#[test]
+fn infinite_search_waits_for_stop_without_holding_the_table() {
+    // Stalemate, the search itself is over at once
+    let fen: Fen = "7k/5Q2/6K1/8/8/8/8/8 b - - 0 1".parse().unwrap();
+    let board: Chess = fen.into_position(CastlingMode::Standard).unwrap();
+    let t_table = Arc::new(Mutex::new(TranspositionTable::new()));
+    let mut search = Some(go("go infinite", &board, &t_table, &EngineOptions::default()));
+
+    thread::sleep(Duration::from_millis(100));
+    assert!(!search.as_ref().unwrap().is_finished());
+    assert!(t_table.try_lock().is_ok());
+
+    stop(&mut search);
+    assert!(search.is_none());
+}